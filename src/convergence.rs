@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+// Struct: `Convergence` ================================================================
+
+/// A stopping rule for iterative solvers: iterate until the largest change in any value
+/// between successive iterates is below `tolerance`, or `max_iters` iterations have been
+/// made.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Convergence {
+    pub tolerance: f64,
+    pub max_iters: usize,
+}
+
+impl Convergence {
+    pub fn new(tolerance: f64, max_iters: usize) -> Self {
+        Convergence {
+            tolerance,
+            max_iters,
+        }
+    }
+
+    /// Returns whether the change from `prev` to `next`, measured by [`max_change`], is
+    /// below the tolerance.
+    pub fn has_converged<K>(&self, prev: &HashMap<K, f64>, next: &HashMap<K, f64>) -> bool
+    where
+        K: Eq + Hash,
+    {
+        self.accepts(max_change(prev, next))
+    }
+
+    /// Returns whether a change of `delta` between successive iterates is below the
+    /// tolerance.
+    pub(crate) fn accepts(&self, delta: f64) -> bool {
+        delta < self.tolerance
+    }
+
+    /// Returns the report for a run that stopped after `iterations` iterations, the last
+    /// of which changed the iterate by `final_delta`.
+    pub(crate) fn report(&self, iterations: usize, final_delta: f64) -> ConvergenceReport {
+        ConvergenceReport {
+            iterations,
+            final_delta,
+            converged: self.accepts(final_delta),
+        }
+    }
+}
+
+/// Returns the max-norm distance maxₖ |next(k) - prev(k)| between two value maps, where a
+/// key missing from one of them has value zero in it.
+pub fn max_change<K>(prev: &HashMap<K, f64>, next: &HashMap<K, f64>) -> f64
+where
+    K: Eq + Hash,
+{
+    let changed = next
+        .iter()
+        .map(|(k, v)| (v - prev.get(k).copied().unwrap_or(0.0)).abs());
+    let removed = prev
+        .iter()
+        .filter(|(k, _)| !next.contains_key(k))
+        .map(|(_, v)| v.abs());
+    changed.chain(removed).fold(0.0, f64::max)
+}
+
+// --------------------------------------------------------------------------------------
+
+// Struct: `ConvergenceReport` ==========================================================
+
+/// The outcome of running an iterative solver under a [`Convergence`] rule.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ConvergenceReport {
+    /// The number of iterations made.
+    pub iterations: usize,
+    /// The largest change in any value made by the last iteration, or infinity if no
+    /// iteration was made.
+    pub final_delta: f64,
+    /// Whether the last change was below the tolerance, as opposed to the solver stopping
+    /// at the iteration limit.
+    pub converged: bool,
+}
+
+// --------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn max_change_treats_missing_keys_as_zero() {
+        let prev = HashMap::from([('a', 1.0), ('b', -3.0)]);
+        let next = HashMap::from([('a', 1.5), ('c', 2.0)]);
+        assert_eq!(max_change(&prev, &next), 3.0);
+        assert_eq!(max_change(&next, &prev), 3.0);
+        assert_eq!(max_change(&prev, &prev), 0.0);
+
+        let convergence = Convergence::new(0.5, 10);
+        assert!(convergence.has_converged(&prev, &prev));
+        assert!(!convergence.has_converged(&prev, &next));
+    }
+}
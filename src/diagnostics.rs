@@ -0,0 +1,201 @@
+use crate::distribution::FiniteDistribution;
+use crate::error::Result;
+use crate::markov_decision_process::FiniteMarkovDecisionProcess;
+use crate::markov_process::{Reward, State, ValueFunction};
+use crate::policy::Policy;
+use std::hash::Hash;
+
+/// Differences in value smaller than this are treated as ties by [`compare_policies`].
+const VALUE_TOLERANCE: f64 = 1e-9;
+
+// Struct: `ValueError` =================================================================
+
+/// Summary statistics of the pointwise error of an estimated value function against a
+/// reference, as returned by [`compare_value_functions`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ValueError {
+    /// The largest absolute error over all states.
+    pub max_abs: f64,
+    /// The root mean squared error.
+    pub rmse: f64,
+    /// The mean absolute error.
+    pub mean_abs: f64,
+}
+
+// --------------------------------------------------------------------------------------
+
+// Struct: `PolicyComparison` ===========================================================
+
+/// Which of two policies has the better value function, as found by [`compare_policies`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dominance {
+    /// The first policy is at least as good in every state and better in some state.
+    First,
+    /// The second policy is at least as good in every state and better in some state.
+    Second,
+    /// Both policies have the same value in every state.
+    Equal,
+    /// Each policy is better than the other in some state.
+    Neither,
+}
+
+/// The value functions of two policies compared state by state, as returned by
+/// [`compare_policies`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct PolicyComparison<S>
+where
+    S: Eq + Hash,
+{
+    /// The difference V₁(s) - V₂(s) between the values of the two policies in each state.
+    pub differences: ValueFunction<S>,
+    /// Which policy, if either, dominates the other.
+    pub dominance: Dominance,
+}
+
+// --------------------------------------------------------------------------------------
+
+// [ Comparisons ] ======================================================================
+
+/// Returns the error of `estimate` against `reference` over the states of `reference`,
+/// such as a Monte Carlo or TD estimate against the exact value function found by
+/// dynamic programming.
+///
+/// A state missing from `estimate` counts as estimated at zero, so its error is its
+/// reference value. States that appear only in `estimate` are ignored. All errors are
+/// zero if `reference` is empty.
+pub fn compare_value_functions<S>(
+    reference: &ValueFunction<S>,
+    estimate: &ValueFunction<S>,
+) -> ValueError
+where
+    S: Eq + Hash,
+{
+    let mut max_abs: f64 = 0.0;
+    let mut sum_abs = 0.0;
+    let mut sum_squared = 0.0;
+    for (state, value) in reference {
+        let error = (value - estimate.get(state).copied().unwrap_or(0.0)).abs();
+        max_abs = max_abs.max(error);
+        sum_abs += error;
+        sum_squared += error * error;
+    }
+
+    let n = reference.len().max(1) as f64;
+    ValueError {
+        max_abs,
+        rmse: (sum_squared / n).sqrt(),
+        mean_abs: sum_abs / n,
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+/// Evaluates the policies `first` and `second` on `mdp` for discount factor `gamma`, by
+/// solving for the value function of [`FiniteMarkovDecisionProcess::apply_policy`], and
+/// returns the difference of their values in each state and which of them dominates.
+///
+/// Differences smaller than 1e-9 in size count as ties, so that two optimal policies come
+/// out [`Dominance::Equal`] despite rounding. Propagates the errors of
+/// [`FiniteMarkovDecisionProcess::apply_policy`] and
+/// [`crate::markov_process::FiniteMarkovRewardProcess::value_function`].
+pub fn compare_policies<S, A, X, P1, P2>(
+    mdp: &FiniteMarkovDecisionProcess<S, A, X>,
+    first: &P1,
+    second: &P2,
+    gamma: f64,
+) -> Result<PolicyComparison<S>>
+where
+    S: Ord + Hash + Clone,
+    A: Ord + Hash,
+    X: FiniteDistribution<(State<S>, Reward)>,
+    P1: Policy<S, A>,
+    P1::ActionDist: FiniteDistribution<A>,
+    P2: Policy<S, A>,
+    P2::ActionDist: FiniteDistribution<A>,
+{
+    let first_vf = mdp.apply_policy(first)?.value_function(gamma)?;
+    let second_vf = mdp.apply_policy(second)?.value_function(gamma)?;
+
+    let differences: ValueFunction<S> = first_vf
+        .iter()
+        .map(|(state, value)| (state.clone(), value - second_vf[state]))
+        .collect();
+    let first_better = differences.values().any(|&d| d > VALUE_TOLERANCE);
+    let second_better = differences.values().any(|&d| d < -VALUE_TOLERANCE);
+    let dominance = match (first_better, second_better) {
+        (true, false) => Dominance::First,
+        (false, true) => Dominance::Second,
+        (false, false) => Dominance::Equal,
+        (true, true) => Dominance::Neither,
+    };
+
+    Ok(PolicyComparison {
+        differences,
+        dominance,
+    })
+}
+
+// --------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convergence::Convergence;
+    use crate::dynamic_programming::value_iteration;
+    use crate::gridworld::{Direction, GridworldBuilder};
+    use crate::markov_process::NonTerminal;
+    use crate::policy::DeterministicPolicy;
+    use std::collections::HashMap;
+
+    #[test]
+    fn value_errors_of_hand_built_maps() {
+        let reference = HashMap::from([
+            (NonTerminal::new('a'), 1.0),
+            (NonTerminal::new('b'), -2.0),
+            (NonTerminal::new('c'), 4.0),
+            (NonTerminal::new('d'), 0.5),
+        ]);
+        // Errors 0, 1, 4 (missing) and 1; the extra state 'e' is ignored.
+        let estimate = HashMap::from([
+            (NonTerminal::new('a'), 1.0),
+            (NonTerminal::new('b'), -1.0),
+            (NonTerminal::new('d'), -0.5),
+            (NonTerminal::new('e'), 100.0),
+        ]);
+        let error = compare_value_functions(&reference, &estimate);
+        assert_eq!(error.max_abs, 4.0);
+        assert_eq!(error.mean_abs, 1.5);
+        assert!((error.rmse - (18.0f64 / 4.0).sqrt()).abs() < 1e-12);
+
+        let none = compare_value_functions(&reference, &reference);
+        assert_eq!((none.max_abs, none.rmse, none.mean_abs), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn optimal_policy_dominates_an_arbitrary_one() {
+        let mdp = GridworldBuilder::new(2, 3)
+            .terminal((0, 2), 10.0)
+            .step_cost(1.0)
+            .build()
+            .unwrap();
+        let (optimal, _, _) =
+            value_iteration(&mdp, 0.9, Convergence::new(1e-12, 10_000), None).unwrap();
+        // Always moving left never reaches the goal.
+        let left = DeterministicPolicy::new(
+            mdp.non_terminal_states()
+                .iter()
+                .map(|s| (*s.state(), Direction::Left))
+                .collect(),
+        );
+
+        let comparison = compare_policies(&mdp, &optimal, &left, 0.9).unwrap();
+        assert_eq!(comparison.dominance, Dominance::First);
+        assert_eq!(comparison.differences.len(), 5);
+        assert!(comparison.differences.values().all(|&d| d >= 0.0));
+
+        let reversed = compare_policies(&mdp, &left, &optimal, 0.9).unwrap();
+        assert_eq!(reversed.dominance, Dominance::Second);
+        let same = compare_policies(&mdp, &optimal, &optimal, 0.9).unwrap();
+        assert_eq!(same.dominance, Dominance::Equal);
+    }
+}
@@ -1,13 +1,50 @@
+use rand::distributions::Open01;
+use rand::{Rng, RngCore};
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fmt::Display;
 use std::hash::Hash;
+use std::sync::OnceLock;
+
+use crate::error::{Error, Result};
 
 // Trait: `Distribution` ================================================================
 
-/// A probability distribution that can be sampled
+/// A probability distribution that can be sampled.
+///
+/// Methods with type parameters make the trait unusable as a trait object; store
+/// distributions as `Box<dyn DynDistribution<T>>` instead, which implements this trait.
 pub trait Distribution<T> {
     /// Returns a random sample from the distribution.
     fn sample(&self) -> T;
 
+    /// Returns a random sample from the distribution drawn using the given random number
+    /// generator, which allows reproducible sampling from a seeded generator.
+    ///
+    /// The default implementation ignores `rng` and forwards to [`Distribution::sample`].
+    fn sample_with<R>(&self, rng: &mut R) -> T
+    where
+        R: Rng + ?Sized,
+    {
+        let _ = rng;
+        self.sample()
+    }
+
+    /// Returns `n` independent samples from the distribution, drawn with
+    /// [`Distribution::sample`]. Unlike [`Distribution::sample_iter`] this borrows `self`.
+    fn sample_n(&self, n: usize) -> Vec<T> {
+        (0..n).map(|_| self.sample()).collect()
+    }
+
+    /// Returns `n` independent samples from the distribution, drawn with
+    /// [`Distribution::sample_with`] using the given random number generator.
+    fn sample_n_with<R>(&self, n: usize, rng: &mut R) -> Vec<T>
+    where
+        R: Rng + ?Sized,
+    {
+        (0..n).map(|_| self.sample_with(rng)).collect()
+    }
+
     /// Create an iterator that generates random values of `T`.
     ///
     /// Note: This function takes `Self` by value.
@@ -21,6 +58,22 @@ pub trait Distribution<T> {
         }
     }
 
+    /// Create an iterator that generates random values of `T` using the given random number
+    /// generator, so that the sequence is reproducible when `rng` is seeded.
+    ///
+    /// Note: This function takes `Self` by value.
+    fn sample_iter_with<R>(self, rng: R) -> DistIterWith<Self, T, R>
+    where
+        Self: Sized,
+        R: Rng,
+    {
+        DistIterWith {
+            dist: self,
+            rng,
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
     /// Apply a function to the outcomes of this distribution by mapping the output of
     /// `Self` through the closure `F`.
     ///
@@ -37,16 +90,37 @@ pub trait Distribution<T> {
         }
     }
 
-    /// Apply a function that returns a distribution to the outcomes of this distribution.
-    /// Allows expression of 'dependent random variables'.
+    /// Apply a stateful function to the outcomes of this distribution, such as one that
+    /// numbers or caches its samples, by mapping the output of `Self` through the `FnMut`
+    /// closure `F`.
+    ///
+    /// Sampling takes `&self`, so the closure is kept in a [`RefCell`] and borrowed
+    /// mutably for each sample. The closure must not sample from the mapped distribution
+    /// itself, which would panic.
+    ///
+    /// Note: This function takes `Self` by value.
+    fn map_mut<Func, U>(self, f: Func) -> DistMapMut<Self, T, Func, U>
+    where
+        Self: Sized,
+        Func: FnMut(T) -> U,
+    {
+        DistMapMut {
+            dist: self,
+            func: RefCell::new(f),
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    /// Apply a function that returns a distribution to the outcomes of this distribution,
+    /// and sample from the distribution it returns. Allows expression of 'dependent random
+    /// variables'.
     ///
     /// Note: This function takes `Self` by value.
-    fn apply<D, Func, U, X>(self, f: Func) -> SampledDist<Self, T, Func, U>
+    fn and_then<Func, U, X>(self, f: Func) -> SampledDist<Self, T, Func, U>
     where
         Self: Sized,
-        D: Distribution<T>,
         X: Distribution<U>,
-        Func: Fn() -> X,
+        Func: Fn(T) -> X,
     {
         SampledDist {
             dist: self,
@@ -55,11 +129,242 @@ pub trait Distribution<T> {
         }
     }
 
+    /// Condition this distribution on `predicate` by rejection sampling: samples are drawn
+    /// until one satisfies the predicate.
+    ///
+    /// Sampling from the result never returns if no outcome satisfies `predicate`; use
+    /// [`DistFilter::try_sample`] to bound the number of attempts.
+    ///
+    /// Note: This function takes `Self` by value.
+    fn filter<P>(self, predicate: P) -> DistFilter<Self, T, P>
+    where
+        Self: Sized,
+        P: Fn(&T) -> bool,
+    {
+        DistFilter {
+            dist: self,
+            predicate,
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    /// Combine this distribution with `other` into the joint distribution of independent
+    /// samples from each.
+    ///
+    /// Note: This function takes `Self` by value.
+    fn zip<D, U>(self, other: D) -> DistZip<Self, D, T, U>
+    where
+        Self: Sized,
+        D: Distribution<U>,
+    {
+        DistZip {
+            first: self,
+            second: other,
+            table: OnceLock::new(),
+        }
+    }
+
     /// Return the expectation of f(X) where X is the random variable for
     /// the distribution and f is an arbitrary function from X to f64.
+    ///
+    /// By default this is [`Distribution::expectation_with`] using the thread-local random
+    /// number generator; distributions that can compute it exactly should override it.
     fn expectation<Func>(&self, f: Func, sample_size: usize) -> f64
     where
-        Func: Fn(&T) -> f64;
+        Func: Fn(&T) -> f64,
+    {
+        self.expectation_with(f, sample_size, &mut rand::thread_rng())
+    }
+
+    /// Return a Monte Carlo estimate of the expectation of f(X) averaged over
+    /// `sample_size` samples drawn with [`Distribution::sample_with`] using the given random
+    /// number generator, so that the estimate is reproducible when `rng` is seeded.
+    fn expectation_with<Func, R>(&self, f: Func, sample_size: usize, rng: &mut R) -> f64
+    where
+        Func: Fn(&T) -> f64,
+        R: Rng + ?Sized,
+    {
+        let sum: f64 = (0..sample_size).map(|_| f(&self.sample_with(rng))).sum();
+        sum / sample_size as f64
+    }
+
+    /// Return the sample variance of f(X) over `sample_size` samples, computed with
+    /// Welford's online algorithm to avoid catastrophic cancellation. The result is `NaN`
+    /// if `sample_size` is less than 2.
+    fn variance<Func>(&self, f: Func, sample_size: usize) -> f64
+    where
+        Func: Fn(&T) -> f64,
+    {
+        self.variance_with(f, sample_size, &mut rand::thread_rng())
+    }
+
+    /// Like [`Distribution::variance`], drawing samples with [`Distribution::sample_with`]
+    /// using the given random number generator. The samples are drawn exactly as by
+    /// [`Distribution::expectation_with`], so the two estimates share their draws when
+    /// given generators seeded alike.
+    fn variance_with<Func, R>(&self, f: Func, sample_size: usize, rng: &mut R) -> f64
+    where
+        Func: Fn(&T) -> f64,
+        R: Rng + ?Sized,
+    {
+        if sample_size < 2 {
+            return f64::NAN;
+        }
+        let mut mean = 0.0;
+        let mut m2 = 0.0;
+        for n in 1..=sample_size {
+            let x = f(&self.sample_with(rng));
+            let delta = x - mean;
+            mean += delta / n as f64;
+            m2 += delta * (x - mean);
+        }
+        m2 / (sample_size as f64 - 1.0)
+    }
+
+    /// Return a Monte Carlo estimate of the expectation of f(X) over `sample_size` samples
+    /// together with the lower and upper bounds of a `confidence`-level interval for it,
+    /// as `(estimate, lower, upper)`.
+    ///
+    /// The interval is the normal approximation estimate ± z·s/√n, where s is the sample
+    /// standard deviation and z the standard normal quantile for `confidence`. It relies on
+    /// the central limit theorem, so it assumes f(X) has finite variance and `sample_size`
+    /// is large; its width shrinks like 1/√n.
+    ///
+    /// Panics if `confidence` is not strictly between 0 and 1.
+    fn expectation_ci<Func>(&self, f: Func, sample_size: usize, confidence: f64) -> (f64, f64, f64)
+    where
+        Func: Fn(&T) -> f64,
+    {
+        self.expectation_ci_with(f, sample_size, confidence, &mut rand::thread_rng())
+    }
+
+    /// Like [`Distribution::expectation_ci`], drawing samples with
+    /// [`Distribution::sample_with`] using the given random number generator.
+    fn expectation_ci_with<Func, R>(
+        &self,
+        f: Func,
+        sample_size: usize,
+        confidence: f64,
+        rng: &mut R,
+    ) -> (f64, f64, f64)
+    where
+        Func: Fn(&T) -> f64,
+        R: Rng + ?Sized,
+    {
+        assert!(
+            confidence > 0.0 && confidence < 1.0,
+            "confidence must be strictly between 0 and 1"
+        );
+        let mut mean = 0.0;
+        let mut m2 = 0.0;
+        for n in 1..=sample_size {
+            let x = f(&self.sample_with(rng));
+            let delta = x - mean;
+            mean += delta / n as f64;
+            m2 += delta * (x - mean);
+        }
+        let n = sample_size as f64;
+        let standard_error = (m2 / (n - 1.0) / n).sqrt();
+        let z = standard_normal_quantile(0.5 + confidence / 2.0);
+        (mean, mean - z * standard_error, mean + z * standard_error)
+    }
+
+    /// Return the sample standard deviation of f(X) over `sample_size` samples.
+    fn std_dev<Func>(&self, f: Func, sample_size: usize) -> f64
+    where
+        Func: Fn(&T) -> f64,
+    {
+        self.variance(f, sample_size).sqrt()
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+// Trait: `DynDistribution` =============================================================
+
+/// The object-safe core of [`Distribution`], implemented for every sized distribution.
+///
+/// [`Distribution`] has generic methods, so heterogeneous distributions are stored as
+/// `Box<dyn DynDistribution<T>>`. The trait object implements [`Distribution`] in turn,
+/// forwarding the caller's random number generator to [`DynDistribution::sample_boxed`],
+/// so all of its methods remain available and reproducible from a seeded generator.
+pub trait DynDistribution<T> {
+    /// Returns a random sample from the distribution drawn using `rng`.
+    fn sample_boxed(&self, rng: &mut dyn RngCore) -> T;
+}
+
+impl<T, D> DynDistribution<T> for D
+where
+    D: Distribution<T>,
+{
+    fn sample_boxed(&self, rng: &mut dyn RngCore) -> T {
+        self.sample_with(rng)
+    }
+}
+
+impl<T> Distribution<T> for dyn DynDistribution<T> + '_ {
+    fn sample(&self) -> T {
+        self.sample_boxed(&mut rand::thread_rng())
+    }
+
+    fn sample_with<R>(&self, mut rng: &mut R) -> T
+    where
+        R: Rng + ?Sized,
+    {
+        self.sample_boxed(&mut rng)
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+/// Returns the quantile Φ⁻¹(p) of the standard normal distribution for 0 < p < 1, using
+/// Acklam's rational approximation (relative error below 1.2e-9).
+fn standard_normal_quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969683028665376e+01,
+        2.209460984245205e+02,
+        -2.759285104469687e+02,
+        1.38357751867269e+02,
+        -3.066479806614716e+01,
+        2.506628277459239e+00,
+    ];
+    const B: [f64; 5] = [
+        -5.447609879822406e+01,
+        1.615858368580409e+02,
+        -1.556989798598866e+02,
+        6.680131188771972e+01,
+        -1.328068155288572e+01,
+    ];
+    const C: [f64; 6] = [
+        -7.784894002430293e-03,
+        -3.223964580411365e-01,
+        -2.400758277161838e+00,
+        -2.549732539343734e+00,
+        4.374664141464968e+00,
+        2.938163982698783e+00,
+    ];
+    const D: [f64; 4] = [
+        7.784695709041462e-03,
+        3.224671290700398e-01,
+        2.445134137142996e+00,
+        3.754408661907416e+00,
+    ];
+    const P_LOW: f64 = 0.02425;
+
+    let tail = |q: f64| {
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    };
+    if p < P_LOW {
+        tail((-2.0 * p.ln()).sqrt())
+    } else if p > 1.0 - P_LOW {
+        -tail((-2.0 * (1.0 - p).ln()).sqrt())
+    } else {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    }
 }
 
 // --------------------------------------------------------------------------------------
@@ -78,6 +383,16 @@ where
     _phantom: std::marker::PhantomData<T>,
 }
 
+impl<D, T> DistIter<D, T>
+where
+    D: Distribution<T>,
+{
+    /// Collect the next `n` samples into a vector.
+    pub fn take_samples(self, n: usize) -> Vec<T> {
+        self.take(n).collect()
+    }
+}
+
 impl<D, T> Iterator for DistIter<D, T>
 where
     D: Distribution<T>,
@@ -90,6 +405,46 @@ where
 
 // --------------------------------------------------------------------------------------
 
+// Struct: `DistIterWith` ===============================================================
+
+/// An iterator that generates random values of `T` with distribution `D`, drawn using the
+/// random number generator `R` that it owns.
+///
+/// This struct is created by the [`Distribution::sample_iter_with`] method.
+#[derive(Debug)]
+pub struct DistIterWith<D, T, R>
+where
+    D: Distribution<T>,
+{
+    dist: D,
+    rng: R,
+    _phantom: std::marker::PhantomData<T>,
+}
+
+impl<D, T, R> DistIterWith<D, T, R>
+where
+    D: Distribution<T>,
+    R: Rng,
+{
+    /// Collect the next `n` samples into a vector.
+    pub fn take_samples(self, n: usize) -> Vec<T> {
+        self.take(n).collect()
+    }
+}
+
+impl<D, T, R> Iterator for DistIterWith<D, T, R>
+where
+    D: Distribution<T>,
+    R: Rng,
+{
+    type Item = T;
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.dist.sample_with(&mut self.rng))
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
 // Struct: `DistMap` ====================================================================
 
 /// A distribution of values of type `U` derived from the distribution `D` by mapping its
@@ -112,12 +467,45 @@ where
         (self.func)(self.dist.sample())
     }
 
-    fn expectation<Func>(&self, f: Func, sample_size: usize) -> f64
+    fn sample_with<R>(&self, rng: &mut R) -> U
     where
-        Func: Fn(&U) -> f64,
+        R: Rng + ?Sized,
     {
-        let sum: f64 = (0..sample_size).map(|_| f(&self.sample())).sum();
-        sum / sample_size as f64
+        (self.func)(self.dist.sample_with(rng))
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+// Struct: `DistMapMut` =================================================================
+
+/// A distribution of values of type `U` derived from the distribution `D` by mapping its
+/// output of type `T` through the stateful closure `F`.
+///
+/// This struct is created by the [`Distribution::map_mut`] method.
+#[derive(Debug)]
+pub struct DistMapMut<D, T, F, U> {
+    dist: D,
+    func: RefCell<F>, // : FnMut(T) -> U
+    _phantom: std::marker::PhantomData<fn(T) -> U>,
+}
+
+impl<D, T, F, U> Distribution<U> for DistMapMut<D, T, F, U>
+where
+    D: Distribution<T>,
+    F: FnMut(T) -> U,
+{
+    fn sample(&self) -> U {
+        let x = self.dist.sample();
+        (self.func.borrow_mut())(x)
+    }
+
+    fn sample_with<R>(&self, rng: &mut R) -> U
+    where
+        R: Rng + ?Sized,
+    {
+        let x = self.dist.sample_with(rng);
+        (self.func.borrow_mut())(x)
     }
 }
 
@@ -125,7 +513,10 @@ where
 
 // Struct: `SampledDist` ================================================================
 
-/// A distribution defined by a function to sample it
+/// A distribution of values of type `U` obtained by sampling from the distribution that
+/// the closure `F` returns for each outcome of type `T` of the distribution `D`.
+///
+/// This struct is created by the [`Distribution::and_then`] method.
 pub struct SampledDist<D, T, F, U> {
     dist: D,
     func: F, // : Fn(T) -> Distribution<U>
@@ -142,66 +533,2050 @@ where
         (self.func)(self.dist.sample()).sample()
     }
 
-    /// Return a sampled approximation of the expectation of f(X) for some f
-    fn expectation<Func>(&self, f: Func, sample_size: usize) -> f64
+    fn sample_with<R>(&self, rng: &mut R) -> U
     where
-        Func: Fn(&U) -> f64,
+        R: Rng + ?Sized,
     {
-        let sum: f64 = (0..sample_size).map(|_| f(&self.sample())).sum();
-        sum / sample_size as f64
+        (self.func)(self.dist.sample_with(rng)).sample_with(rng)
     }
 }
 
-// Trait: `FiniteDistribution` ==========================================================
+// --------------------------------------------------------------------------------------
 
-/// A probability distribution with a finite number of outcomes,
-/// which means we can render it as a PDF or CDF table
-pub trait FiniteDistribution<T>: Distribution<T>
+// Struct: `DistFilter` =================================================================
+
+/// A distribution of the outcomes of the distribution `D` that satisfy the predicate `P`,
+/// sampled by rejection.
+///
+/// This struct is created by the [`Distribution::filter`] method.
+#[derive(Debug)]
+pub struct DistFilter<D, T, P> {
+    dist: D,
+    predicate: P, // : Fn(&T) -> bool
+    _phantom: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<D, T, P> DistFilter<D, T, P>
 where
-    T: Eq + Hash,
+    D: Distribution<T>,
+    P: Fn(&T) -> bool,
 {
-    /// Returns a tabular representation of the probability density function (PDF) for
-    /// this distribution.
-    fn table(&self) -> &HashMap<&T, f64>;
+    /// Returns a sample satisfying the predicate, or `None` if none was found within
+    /// `max_attempts` draws.
+    pub fn try_sample(&self, max_attempts: usize) -> Option<T> {
+        self.try_sample_with(max_attempts, &mut rand::thread_rng())
+    }
 
-    /// Returns the probability of the given outcome according to this distribution.
-    fn probability(&self, outcome: &T) -> f64 {
-        self.table()
-            .get(outcome)
-            .map(|x| x.to_owned())
-            .unwrap_or(0.0)
+    /// Like [`DistFilter::try_sample`], drawing samples using the given random number
+    /// generator.
+    pub fn try_sample_with<R>(&self, max_attempts: usize, rng: &mut R) -> Option<T>
+    where
+        R: Rng + ?Sized,
+    {
+        (0..max_attempts)
+            .map(|_| self.dist.sample_with(rng))
+            .find(|x| (self.predicate)(x))
     }
+}
 
-    // Calculate the expected value of the distribution, using the given function
-    fn expectation<Func>(&self, f: Func, sample_size: usize) -> f64
+impl<D, T, P> Distribution<T> for DistFilter<D, T, P>
+where
+    D: Distribution<T>,
+    P: Fn(&T) -> bool,
+{
+    fn sample(&self) -> T {
+        loop {
+            let x = self.dist.sample();
+            if (self.predicate)(&x) {
+                return x;
+            }
+        }
+    }
+
+    fn sample_with<R>(&self, rng: &mut R) -> T
     where
-        Self: Sized,
-        Func: Fn(&T) -> f64,
+        R: Rng + ?Sized,
     {
-        let sum: f64 = self.table().into_iter().map(|(&k, &v)| v * f(k)).sum();
-        sum / sample_size as f64
+        loop {
+            let x = self.dist.sample_with(rng);
+            if (self.predicate)(&x) {
+                return x;
+            }
+        }
     }
 }
 
 // --------------------------------------------------------------------------------------
 
-// [ Finite Distributions ] =============================================================
+// Struct: `DistZip` ====================================================================
+
+/// The joint distribution of independent samples from the distributions `D1` and `D2`.
+///
+/// This struct is created by the [`Distribution::zip`] method. If both distributions are
+/// finite the joint distribution is too, with the product of their tables as its table.
+#[derive(Debug)]
+pub struct DistZip<D1, D2, T, U> {
+    first: D1,
+    second: D2,
+    table: OnceLock<HashMap<(T, U), f64>>,
+}
 
-// pub struct Categorical<'a, A> {
-//     probabilities: HashMap<&'a A, f64>,
-// }
+impl<D1, D2, T, U> Distribution<(T, U)> for DistZip<D1, D2, T, U>
+where
+    D1: Distribution<T>,
+    D2: Distribution<U>,
+{
+    fn sample(&self) -> (T, U) {
+        (self.first.sample(), self.second.sample())
+    }
 
-// impl<'a, A: Eq + Hash> Distribution<A> for Categorical<'a, A> {
-//     fn sample(&self) -> A {
-//         let (a, _) = self.probabilities.iter().next().unwrap();
-//         *a.clone()
-//     }
-// }
+    fn sample_with<R>(&self, rng: &mut R) -> (T, U)
+    where
+        R: Rng + ?Sized,
+    {
+        (self.first.sample_with(rng), self.second.sample_with(rng))
+    }
+}
 
-// impl<'a, A: Eq + Hash> FiniteDistribution<A> for Categorical<'a, A> {
-//     fn table(&self) -> &HashMap<&A, f64> {
-//         &self.probabilities
-//     }
-// }
+impl<D1, D2, T, U> FiniteDistribution<(T, U)> for DistZip<D1, D2, T, U>
+where
+    D1: FiniteDistribution<T>,
+    D2: FiniteDistribution<U>,
+    T: Eq + Hash + Clone,
+    U: Eq + Hash + Clone,
+{
+    fn table(&self) -> &HashMap<(T, U), f64> {
+        self.table.get_or_init(|| {
+            let mut table = HashMap::new();
+            for (t, &p) in self.first.table() {
+                for (u, &q) in self.second.table() {
+                    table.insert((t.clone(), u.clone()), p * q);
+                }
+            }
+            table
+        })
+    }
+}
 
 // --------------------------------------------------------------------------------------
+
+// Struct: `Mixture` ====================================================================
+
+/// A weighted mixture of component distributions: a sample is drawn by first picking a
+/// component with probability proportional to its weight, then sampling from it.
+///
+/// Components are boxed values of type `D`, so with the default `D = dyn DynDistribution<T>`
+/// they may be of different types. If they share a finite distribution type, the mixture
+/// also exposes the combined table Σ wᵢ · pᵢ(x) through [`FiniteDistribution`].
+pub struct Mixture<T, D = dyn DynDistribution<T>>
+where
+    D: ?Sized,
+{
+    components: Vec<(Box<D>, f64)>,
+    table: OnceLock<HashMap<T, f64>>,
+}
+
+impl<T, D> Mixture<T, D>
+where
+    D: DynDistribution<T> + ?Sized,
+{
+    /// Create a mixture from pairs of components and weights. The weights are normalized
+    /// so that they sum to 1.
+    ///
+    /// Returns [`Error::InvalidWeights`] if any weight is negative or not finite, or if
+    /// the weights sum to zero.
+    pub fn new(components: Vec<(Box<D>, f64)>) -> Result<Self> {
+        if components.iter().any(|&(_, w)| !w.is_finite() || w < 0.0) {
+            return Err(Error::InvalidWeights);
+        }
+        let total: f64 = components.iter().map(|&(_, w)| w).sum();
+        if total <= 0.0 {
+            return Err(Error::InvalidWeights);
+        }
+
+        let components = components
+            .into_iter()
+            .map(|(component, w)| (component, w / total))
+            .collect();
+        Ok(Mixture {
+            components,
+            table: OnceLock::new(),
+        })
+    }
+
+    /// Returns the components together with their normalized weights.
+    pub fn components(&self) -> &[(Box<D>, f64)] {
+        &self.components
+    }
+}
+
+impl<T, D> Distribution<T> for Mixture<T, D>
+where
+    D: DynDistribution<T> + ?Sized,
+{
+    fn sample(&self) -> T {
+        self.sample_with(&mut rand::thread_rng())
+    }
+
+    fn sample_with<R>(&self, mut rng: &mut R) -> T
+    where
+        R: Rng + ?Sized,
+    {
+        let u: f64 = rng.gen();
+        let mut cumulative = 0.0;
+        for (component, w) in &self.components {
+            cumulative += w;
+            if u < cumulative {
+                return component.sample_boxed(&mut rng);
+            }
+        }
+        let (component, _) = self.components.last().expect("mixture has components");
+        component.sample_boxed(&mut rng)
+    }
+}
+
+impl<T, D> FiniteDistribution<T> for Mixture<T, D>
+where
+    T: Eq + Hash + Clone,
+    D: FiniteDistribution<T>,
+{
+    fn table(&self) -> &HashMap<T, f64> {
+        self.table.get_or_init(|| {
+            let mut table = HashMap::new();
+            for (component, w) in &self.components {
+                for (x, &p) in component.table() {
+                    *table.entry(x.clone()).or_insert(0.0) += w * p;
+                }
+            }
+            table
+        })
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+// Trait: `FiniteDistribution` ==========================================================
+
+/// A probability distribution with a finite number of outcomes,
+/// which means we can render it as a PDF or CDF table
+pub trait FiniteDistribution<T>: Distribution<T>
+where
+    T: Eq + Hash,
+{
+    /// Returns a tabular representation of the probability density function (PDF) for
+    /// this distribution.
+    fn table(&self) -> &HashMap<T, f64>;
+
+    /// Returns the probability of the given outcome according to this distribution.
+    fn probability(&self, outcome: &T) -> f64 {
+        self.table()
+            .get(outcome)
+            .map(|x| x.to_owned())
+            .unwrap_or(0.0)
+    }
+
+    /// Returns the cumulative distribution function as the outcomes in ascending order,
+    /// each paired with the total probability of the outcomes up to and including it. The
+    /// last cumulative probability is 1 up to rounding.
+    fn cdf_table(&self) -> Vec<(T, f64)>
+    where
+        T: Ord + Clone,
+    {
+        let mut outcomes: Vec<(&T, f64)> = self.table().iter().map(|(x, &p)| (x, p)).collect();
+        outcomes.sort_by_key(|(x, _)| *x);
+        let mut cumulative = 0.0;
+        outcomes
+            .into_iter()
+            .map(|(x, p)| {
+                cumulative += p;
+                (x.clone(), cumulative)
+            })
+            .collect()
+    }
+
+    /// Returns this distribution conditioned on `predicate`: the outcomes satisfying it,
+    /// with their probabilities renormalized to sum to 1. This is the exact counterpart of
+    /// the rejection sampling [`Distribution::filter`].
+    ///
+    /// Returns [`Error::Empty`] if no outcome satisfies `predicate`, and
+    /// [`Error::InvalidWeights`] if none of those that do has positive probability.
+    fn condition_on<P>(&self, predicate: P) -> Result<Categorical<T>>
+    where
+        T: Clone,
+        P: Fn(&T) -> bool,
+    {
+        let weights = self
+            .table()
+            .iter()
+            .filter(|(x, _)| predicate(x))
+            .map(|(x, &p)| (x.clone(), p))
+            .collect();
+        Categorical::new(weights)
+    }
+
+    /// Returns the exact expectation Σ p(x) · f(x) of f(X) computed from the table.
+    ///
+    /// Note: when [`Distribution`] is also in scope, call this as
+    /// `FiniteDistribution::expectation(&dist, f)` to disambiguate it from the sampled
+    /// [`Distribution::expectation`].
+    fn expectation<Func>(&self, f: Func) -> f64
+    where
+        Func: Fn(&T) -> f64,
+    {
+        self.table().iter().map(|(k, &v)| v * f(k)).sum()
+    }
+
+    /// Returns the entropy -Σ p(x) · ln p(x) of the distribution in nats. Outcomes with zero
+    /// probability contribute nothing.
+    fn entropy(&self) -> f64 {
+        -self
+            .table()
+            .values()
+            .filter(|&&p| p > 0.0)
+            .map(|&p| p * p.ln())
+            .sum::<f64>()
+    }
+
+    /// Returns the most probable outcome, or `None` if the table is empty. Ties are broken
+    /// in favour of the least outcome, so the result does not depend on the iteration
+    /// order of the table.
+    fn mode(&self) -> Option<&T>
+    where
+        T: Ord,
+    {
+        self.table()
+            .iter()
+            .max_by(|(x, p), (y, q)| p.total_cmp(q).then_with(|| y.cmp(x)))
+            .map(|(x, _)| x)
+    }
+
+    /// Returns the outcome maximizing `f`, or `None` if the table is empty. Ties are broken
+    /// in favour of the least outcome, as for [`FiniteDistribution::mode`].
+    fn arg_max_by<Func>(&self, f: Func) -> Option<&T>
+    where
+        T: Ord,
+        Func: Fn(&T) -> f64,
+    {
+        self.table()
+            .keys()
+            .map(|x| (x, f(x)))
+            .max_by(|(x, a), (y, b)| a.total_cmp(b).then_with(|| y.cmp(x)))
+            .map(|(x, _)| x)
+    }
+
+    /// Returns `k` distinct outcomes sampled without replacement, each draw picking among
+    /// the remaining outcomes with probability proportional to their probabilities. The
+    /// outcomes are returned in the order they are drawn.
+    ///
+    /// Uses the Efraimidis–Spirakis method: each outcome x with probability p(x) > 0 gets
+    /// the key u^(1 / p(x)) for a uniform draw u, and the `k` largest keys are taken. The
+    /// keys are drawn in ascending order of the outcomes, so a seeded `rng` gives the same
+    /// outcomes for equal tables.
+    ///
+    /// Returns [`Error::InvalidParameter`], without drawing from `rng`, if `k` exceeds the
+    /// number of outcomes with positive probability.
+    fn sample_without_replacement<R>(&self, k: usize, rng: &mut R) -> Result<Vec<T>>
+    where
+        T: Ord + Clone,
+        R: Rng + ?Sized,
+    {
+        let mut outcomes: Vec<(&T, f64)> = self
+            .table()
+            .iter()
+            .filter(|(_, &p)| p > 0.0)
+            .map(|(x, &p)| (x, p))
+            .collect();
+        if k > outcomes.len() {
+            return Err(Error::InvalidParameter {
+                name: "k",
+                value: k as f64,
+            });
+        }
+        outcomes.sort_by_key(|&(x, _)| x);
+
+        // Compare keys by their logarithms ln(u) / p(x), which do not underflow.
+        let mut keyed: Vec<(f64, &T)> = outcomes
+            .into_iter()
+            .map(|(x, p)| (rng.sample::<f64, _>(Open01).ln() / p, x))
+            .collect();
+        keyed.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        Ok(keyed.into_iter().take(k).map(|(_, x)| x.clone()).collect())
+    }
+
+    /// Returns the table formatted one outcome per line as `outcome: 0.1234`, sorted by
+    /// descending probability. Outcomes with equal probability are ordered by their
+    /// formatted text, so the output is deterministic.
+    fn pretty(&self) -> String
+    where
+        T: Display,
+    {
+        let mut rows: Vec<(String, f64)> = self
+            .table()
+            .iter()
+            .map(|(x, &p)| (x.to_string(), p))
+            .collect();
+        rows.sort_by(|(x, p), (y, q)| q.total_cmp(p).then_with(|| x.cmp(y)));
+        rows.iter()
+            .map(|(x, p)| format!("{}: {:.4}", x, p))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+// [ Sampling ] =========================================================================
+
+/// Samples an outcome of the finite distribution `dist` by inverse-CDF sampling: walks the
+/// table accumulating probability mass, and returns the first outcome at which it exceeds a
+/// uniform draw from [0, 1). If rounding leaves the total mass at or below the draw, the
+/// last outcome visited is returned.
+///
+/// The table is walked in ascending order of the outcomes rather than the iteration order
+/// of the map, which differs between instances, so a seeded `rng` gives the same sample
+/// for equal tables.
+/// This is the natural [`Distribution::sample_with`] for any finite distribution.
+///
+/// Panics if the table is empty.
+pub fn sample_finite<T, D, R>(dist: &D, rng: &mut R) -> T
+where
+    T: Ord + Hash + Clone,
+    D: FiniteDistribution<T> + ?Sized,
+    R: Rng + ?Sized,
+{
+    let mut outcomes: Vec<(&T, f64)> = dist.table().iter().map(|(x, &p)| (x, p)).collect();
+    outcomes.sort_by_key(|&(x, _)| x);
+    sample_ordered(outcomes, rng).clone()
+}
+
+/// Inverse-CDF sampling as for [`sample_finite`], over `outcomes` in the given order.
+///
+/// Panics if `outcomes` is empty.
+fn sample_ordered<X, I, R>(outcomes: I, rng: &mut R) -> X
+where
+    I: IntoIterator<Item = (X, f64)>,
+    R: Rng + ?Sized,
+{
+    let u: f64 = rng.gen();
+    let mut cumulative = 0.0;
+    let mut last = None;
+    for (outcome, p) in outcomes {
+        cumulative += p;
+        if u < cumulative {
+            return outcome;
+        }
+        last = Some(outcome);
+    }
+    last.expect("finite distribution has no outcomes")
+}
+
+// --------------------------------------------------------------------------------------
+
+// [ Marginals ] ========================================================================
+
+/// Returns the distribution of `project(X)` for X distributed as `dist`, summing the
+/// probabilities of outcomes that project to the same value. With a projection onto one
+/// component of a joint distribution this is its marginal, such as the distribution of
+/// successor states obtained by discarding the rewards.
+///
+/// Returns [`Error::InvalidWeights`] if the table of `dist` has no probability mass.
+pub fn marginalize<J, T, D, F>(dist: &D, project: F) -> Result<Categorical<T>>
+where
+    J: Eq + Hash,
+    T: Eq + Hash,
+    D: FiniteDistribution<J> + ?Sized,
+    F: Fn(&J) -> T,
+{
+    let mut weights = HashMap::new();
+    for (outcome, &p) in dist.table() {
+        *weights.entry(project(outcome)).or_insert(0.0) += p;
+    }
+    Categorical::new(weights)
+}
+
+// --------------------------------------------------------------------------------------
+
+// [ Divergences ] ======================================================================
+
+/// Returns the Kullback-Leibler divergence KL(p ‖ q) = Σ p(x) · ln(p(x) / q(x)) in nats.
+///
+/// The divergence is infinite if `q` assigns zero probability to an outcome that `p`
+/// supports.
+pub fn kl_divergence<T, P, Q>(p: &P, q: &Q) -> f64
+where
+    T: Eq + Hash,
+    P: FiniteDistribution<T>,
+    Q: FiniteDistribution<T>,
+{
+    let mut divergence = 0.0;
+    for (x, &p_x) in p.table() {
+        if p_x > 0.0 {
+            let q_x = q.probability(x);
+            if q_x <= 0.0 {
+                return f64::INFINITY;
+            }
+            divergence += p_x * (p_x / q_x).ln();
+        }
+    }
+    divergence
+}
+
+/// Returns the total variation distance ½ Σ |p(x) - q(x)| between `p` and `q`, summed
+/// over the union of their supports. Outcomes missing from one table have probability
+/// zero in it. The distance lies in [0, 1].
+pub fn total_variation<T, P, Q>(p: &P, q: &Q) -> f64
+where
+    T: Eq + Hash,
+    P: FiniteDistribution<T>,
+    Q: FiniteDistribution<T>,
+{
+    let shared_and_p: f64 = p
+        .table()
+        .iter()
+        .map(|(x, &p_x)| (p_x - q.probability(x)).abs())
+        .sum();
+    let only_q: f64 = q
+        .table()
+        .iter()
+        .filter(|(x, _)| !p.table().contains_key(x))
+        .map(|(_, &q_x)| q_x.abs())
+        .sum();
+    (shared_and_p + only_q) / 2.0
+}
+
+// --------------------------------------------------------------------------------------
+
+// [ Importance Sampling ] ==============================================================
+
+/// Returns an importance sampling estimate of E_p[f(X)] for a target distribution p
+/// known only through its unnormalized log-density `target_log_density`, from
+/// `sample_size` samples of `proposal`.
+///
+/// Each sample x is weighted by p(x) / q(x), and the estimate is the weighted average of
+/// f(x). Since p is unnormalized the weights are normalized by their sum (self-normalized
+/// importance sampling), so the estimate is consistent but slightly biased for small
+/// samples. Samples at which the proposal density is zero are discarded. Returns `NaN`
+/// if no sample has positive weight.
+pub fn importance_expectation<T, P, Q, F>(
+    target_log_density: P,
+    proposal: &Q,
+    f: F,
+    sample_size: usize,
+) -> f64
+where
+    P: Fn(&T) -> f64,
+    Q: Density<T>,
+    F: Fn(&T) -> f64,
+{
+    importance_expectation_with(
+        target_log_density,
+        proposal,
+        f,
+        sample_size,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// Like [`importance_expectation`], sampling the proposal using the given random number
+/// generator.
+pub fn importance_expectation_with<T, P, Q, F, R>(
+    target_log_density: P,
+    proposal: &Q,
+    f: F,
+    sample_size: usize,
+    rng: &mut R,
+) -> f64
+where
+    P: Fn(&T) -> f64,
+    Q: Density<T>,
+    F: Fn(&T) -> f64,
+    R: Rng + ?Sized,
+{
+    let mut samples = Vec::with_capacity(sample_size);
+    for _ in 0..sample_size {
+        let x = proposal.sample_with(rng);
+        let log_q = proposal.log_density(&x);
+        if log_q.is_finite() {
+            samples.push((target_log_density(&x) - log_q, x));
+        }
+    }
+
+    // Subtract the largest log-weight so that exponentiating cannot overflow.
+    let max_log_weight = samples
+        .iter()
+        .map(|&(log_weight, _)| log_weight)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let mut weighted_sum = 0.0;
+    let mut total_weight = 0.0;
+    for (log_weight, x) in &samples {
+        let weight = (log_weight - max_log_weight).exp();
+        weighted_sum += weight * f(x);
+        total_weight += weight;
+    }
+    weighted_sum / total_weight
+}
+
+// --------------------------------------------------------------------------------------
+
+// [ Finite Distributions ] =============================================================
+
+// Struct: `Categorical` ================================================================
+
+/// A finite distribution over arbitrary outcomes of type `A` given by an explicit table of
+/// probabilities.
+#[derive(Clone, Debug)]
+pub struct Categorical<A>
+where
+    A: Eq + Hash,
+{
+    probabilities: HashMap<A, f64>,
+    /// The table in ascending order of the outcomes for sampling, built on first use.
+    ordered: OnceLock<Vec<(A, f64)>>,
+}
+
+impl<A> Categorical<A>
+where
+    A: Eq + Hash,
+{
+    /// Create a categorical distribution from a map of outcomes to weights. The weights
+    /// are normalized so that they sum to 1.
+    ///
+    /// Returns [`Error::Empty`] if `weights` is empty, and [`Error::InvalidWeights`] if any
+    /// weight is negative or not finite, or if the weights sum to zero.
+    pub fn new(weights: HashMap<A, f64>) -> Result<Self> {
+        if weights.is_empty() {
+            return Err(Error::Empty);
+        }
+        if weights.values().any(|&w| !w.is_finite() || w < 0.0) {
+            return Err(Error::InvalidWeights);
+        }
+        let total: f64 = weights.values().sum();
+        if total <= 0.0 {
+            return Err(Error::InvalidWeights);
+        }
+
+        let probabilities = weights.into_iter().map(|(a, w)| (a, w / total)).collect();
+        Ok(Categorical {
+            probabilities,
+            ordered: OnceLock::new(),
+        })
+    }
+
+    /// Create a categorical distribution from a map of outcomes to the number of times
+    /// each was observed, such as visit counts tallied from simulated traces. Each
+    /// outcome gets its share of the total count.
+    ///
+    /// Returns [`Error::Empty`] if `counts` is empty, and [`Error::InvalidWeights`] if the
+    /// counts sum to zero.
+    pub fn from_counts(counts: HashMap<A, u64>) -> Result<Self> {
+        Categorical::new(counts.into_iter().map(|(a, c)| (a, c as f64)).collect())
+    }
+}
+
+impl<A> PartialEq for Categorical<A>
+where
+    A: Eq + Hash,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.probabilities == other.probabilities
+    }
+}
+
+/// Serialized as a sequence of `(outcome, probability)` pairs, since outcomes need not be
+/// valid map keys in formats such as JSON.
+#[cfg(feature = "serde")]
+impl<A> serde::Serialize for Categorical<A>
+where
+    A: Eq + Hash + serde::Serialize,
+{
+    fn serialize<Ser>(&self, serializer: Ser) -> std::result::Result<Ser::Ok, Ser::Error>
+    where
+        Ser: serde::Serializer,
+    {
+        serializer.collect_seq(&self.probabilities)
+    }
+}
+
+/// Deserialized from a sequence of `(outcome, weight)` pairs through [`Categorical::new`],
+/// so the weights are validated and normalized.
+#[cfg(feature = "serde")]
+impl<'de, A> serde::Deserialize<'de> for Categorical<A>
+where
+    A: Eq + Hash + serde::Deserialize<'de>,
+{
+    fn deserialize<De>(deserializer: De) -> std::result::Result<Self, De::Error>
+    where
+        De: serde::Deserializer<'de>,
+    {
+        let pairs: Vec<(A, f64)> = serde::Deserialize::deserialize(deserializer)?;
+        let mut weights = HashMap::with_capacity(pairs.len());
+        for (outcome, weight) in pairs {
+            *weights.entry(outcome).or_insert(0.0) += weight;
+        }
+        Categorical::new(weights).map_err(serde::de::Error::custom)
+    }
+}
+
+impl<A> Distribution<A> for Categorical<A>
+where
+    A: Ord + Hash + Clone,
+{
+    fn sample(&self) -> A {
+        self.sample_with(&mut rand::thread_rng())
+    }
+
+    /// Samples as [`sample_finite`] does, walking the table in an order computed once and
+    /// kept with the distribution.
+    fn sample_with<R>(&self, rng: &mut R) -> A
+    where
+        R: Rng + ?Sized,
+    {
+        let ordered = self.ordered.get_or_init(|| {
+            let mut ordered: Vec<(A, f64)> = self
+                .probabilities
+                .iter()
+                .map(|(a, &p)| (a.clone(), p))
+                .collect();
+            ordered.sort_by(|(a, _), (b, _)| a.cmp(b));
+            ordered
+        });
+        sample_ordered(ordered.iter().map(|(a, p)| (a, *p)), rng).clone()
+    }
+
+    /// The expectation is computed exactly from the table, so `sample_size` is ignored.
+    fn expectation<Func>(&self, f: Func, _sample_size: usize) -> f64
+    where
+        Func: Fn(&A) -> f64,
+    {
+        self.probabilities.iter().map(|(a, &p)| p * f(a)).sum()
+    }
+}
+
+impl<A> FiniteDistribution<A> for Categorical<A>
+where
+    A: Ord + Hash + Clone,
+{
+    fn table(&self) -> &HashMap<A, f64> {
+        &self.probabilities
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+// Struct: `Constant` ===================================================================
+
+/// A degenerate distribution that always yields the same value.
+#[derive(Clone, Debug)]
+pub struct Constant<T> {
+    value: T,
+    table: OnceLock<HashMap<T, f64>>,
+}
+
+impl<T> Constant<T> {
+    pub fn new(value: T) -> Self {
+        Constant {
+            value,
+            table: OnceLock::new(),
+        }
+    }
+
+    /// Returns a reference to the value this distribution always yields.
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> PartialEq for Constant<T>
+where
+    T: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<T> Distribution<T> for Constant<T>
+where
+    T: Clone,
+{
+    fn sample(&self) -> T {
+        self.value.clone()
+    }
+
+    fn sample_with<R>(&self, _rng: &mut R) -> T
+    where
+        R: Rng + ?Sized,
+    {
+        self.value.clone()
+    }
+
+    /// The expectation of a constant is exact, so `sample_size` is ignored.
+    fn expectation<Func>(&self, f: Func, _sample_size: usize) -> f64
+    where
+        Func: Fn(&T) -> f64,
+    {
+        f(&self.value)
+    }
+}
+
+impl<T> FiniteDistribution<T> for Constant<T>
+where
+    T: Eq + Hash + Clone,
+{
+    fn table(&self) -> &HashMap<T, f64> {
+        self.table
+            .get_or_init(|| HashMap::from([(self.value.clone(), 1.0)]))
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+// Struct: `Bernoulli` ==================================================================
+
+/// A distribution over `bool` that yields `true` with probability `p`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bernoulli {
+    p: f64,
+    table: HashMap<bool, f64>,
+}
+
+impl Bernoulli {
+    /// Returns [`Error::InvalidProbability`] unless `p` lies in [0, 1].
+    pub fn new(p: f64) -> Result<Self> {
+        if !(0.0..=1.0).contains(&p) {
+            return Err(Error::InvalidProbability(p));
+        }
+        let table = HashMap::from([(true, p), (false, 1.0 - p)]);
+        Ok(Bernoulli { p, table })
+    }
+
+    /// Returns the probability of `true`.
+    pub fn p(&self) -> f64 {
+        self.p
+    }
+}
+
+impl Distribution<bool> for Bernoulli {
+    fn sample(&self) -> bool {
+        self.sample_with(&mut rand::thread_rng())
+    }
+
+    fn sample_with<R>(&self, rng: &mut R) -> bool
+    where
+        R: Rng + ?Sized,
+    {
+        rng.gen::<f64>() < self.p
+    }
+
+    /// The expectation is computed exactly from the table, so `sample_size` is ignored.
+    fn expectation<Func>(&self, f: Func, _sample_size: usize) -> f64
+    where
+        Func: Fn(&bool) -> f64,
+    {
+        self.p * f(&true) + (1.0 - self.p) * f(&false)
+    }
+}
+
+impl FiniteDistribution<bool> for Bernoulli {
+    fn table(&self) -> &HashMap<bool, f64> {
+        &self.table
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+// Struct: `Binomial` ===================================================================
+
+/// A binomial distribution over the number of successes in `n` independent trials, each
+/// succeeding with probability `p`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Binomial {
+    n: u64,
+    p: f64,
+    table: HashMap<u64, f64>,
+}
+
+impl Binomial {
+    /// Returns [`Error::InvalidProbability`] unless `p` lies in [0, 1].
+    ///
+    /// The table of n + 1 probabilities is computed up front in log space, with the
+    /// binomial coefficients built up as ln C(n, k) = ln C(n, k - 1) + ln(n - k + 1) - ln k,
+    /// so large `n` does not overflow.
+    pub fn new(n: u64, p: f64) -> Result<Self> {
+        if !(0.0..=1.0).contains(&p) {
+            return Err(Error::InvalidProbability(p));
+        }
+
+        // k · ln(q), taking 0 · ln(0) to be 0 so that p = 0 and p = 1 work.
+        let log_power = |k: u64, q: f64| if k == 0 { 0.0 } else { k as f64 * q.ln() };
+        let mut table = HashMap::with_capacity(n as usize + 1);
+        let mut log_choose = 0.0;
+        for k in 0..=n {
+            if k > 0 {
+                log_choose += ((n - k + 1) as f64).ln() - (k as f64).ln();
+            }
+            let log_p = log_choose + log_power(k, p) + log_power(n - k, 1.0 - p);
+            table.insert(k, log_p.exp());
+        }
+        Ok(Binomial { n, p, table })
+    }
+
+    /// Returns the number of trials.
+    pub fn n(&self) -> u64 {
+        self.n
+    }
+
+    /// Returns the probability of success on each trial.
+    pub fn p(&self) -> f64 {
+        self.p
+    }
+}
+
+impl Distribution<u64> for Binomial {
+    fn sample(&self) -> u64 {
+        self.sample_with(&mut rand::thread_rng())
+    }
+
+    /// Samples as [`sample_finite`] does, walking the outcomes in increasing order.
+    fn sample_with<R>(&self, rng: &mut R) -> u64
+    where
+        R: Rng + ?Sized,
+    {
+        sample_ordered((0..=self.n).map(|k| (k, self.table[&k])), rng)
+    }
+
+    /// The expectation is computed exactly from the table, so `sample_size` is ignored.
+    fn expectation<Func>(&self, f: Func, _sample_size: usize) -> f64
+    where
+        Func: Fn(&u64) -> f64,
+    {
+        self.table.iter().map(|(k, &p)| p * f(k)).sum()
+    }
+}
+
+impl FiniteDistribution<u64> for Binomial {
+    fn table(&self) -> &HashMap<u64, f64> {
+        &self.table
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+// Struct: `Choose` =====================================================================
+
+/// A discrete uniform distribution that picks one of a list of options with equal
+/// probability. Options listed more than once are correspondingly more likely.
+///
+/// Sampling only needs `T: Clone`; the table is built on first use.
+#[derive(Clone, Debug)]
+pub struct Choose<T> {
+    options: Vec<T>,
+    table: OnceLock<HashMap<T, f64>>,
+}
+
+impl<T> Choose<T> {
+    /// Create a uniform distribution over `options`.
+    ///
+    /// Returns [`Error::Empty`] if there are no options.
+    pub fn new(options: Vec<T>) -> Result<Self> {
+        if options.is_empty() {
+            return Err(Error::Empty);
+        }
+        Ok(Choose {
+            options,
+            table: OnceLock::new(),
+        })
+    }
+
+    /// Returns the options this distribution picks from.
+    pub fn options(&self) -> &[T] {
+        &self.options
+    }
+}
+
+impl<T> Distribution<T> for Choose<T>
+where
+    T: Clone,
+{
+    fn sample(&self) -> T {
+        self.sample_with(&mut rand::thread_rng())
+    }
+
+    fn sample_with<R>(&self, rng: &mut R) -> T
+    where
+        R: Rng + ?Sized,
+    {
+        self.options[rng.gen_range(0..self.options.len())].clone()
+    }
+
+    /// The expectation is computed exactly from the options, so `sample_size` is ignored.
+    fn expectation<Func>(&self, f: Func, _sample_size: usize) -> f64
+    where
+        Func: Fn(&T) -> f64,
+    {
+        self.options.iter().map(f).sum::<f64>() / self.options.len() as f64
+    }
+}
+
+impl<T> FiniteDistribution<T> for Choose<T>
+where
+    T: Eq + Hash + Clone,
+{
+    fn table(&self) -> &HashMap<T, f64> {
+        self.table.get_or_init(|| {
+            let weight = 1.0 / self.options.len() as f64;
+            let mut table = HashMap::new();
+            for x in &self.options {
+                *table.entry(x.clone()).or_insert(0.0) += weight;
+            }
+            table
+        })
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+// Struct: `Empirical` ==================================================================
+
+/// The empirical distribution of a collection of observed samples, which yields each
+/// stored observation with equal probability. Repeated observations are aggregated, so the
+/// probability of a value is its relative frequency in the data.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Empirical<T>
+where
+    T: Eq + Hash,
+{
+    observations: Vec<T>,
+    table: HashMap<T, f64>,
+}
+
+impl<T> Empirical<T>
+where
+    T: Eq + Hash + Clone,
+{
+    /// Create the empirical distribution of `observations`.
+    ///
+    /// Returns [`Error::Empty`] if there are no observations.
+    pub fn new<I>(observations: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let observations: Vec<T> = observations.into_iter().collect();
+        if observations.is_empty() {
+            return Err(Error::Empty);
+        }
+
+        let weight = 1.0 / observations.len() as f64;
+        let mut table = HashMap::new();
+        for x in &observations {
+            *table.entry(x.clone()).or_insert(0.0) += weight;
+        }
+        Ok(Empirical {
+            observations,
+            table,
+        })
+    }
+
+    /// Returns the observations this distribution was built from, in their original order.
+    pub fn observations(&self) -> &[T] {
+        &self.observations
+    }
+}
+
+impl<T> Distribution<T> for Empirical<T>
+where
+    T: Eq + Hash + Clone,
+{
+    fn sample(&self) -> T {
+        self.sample_with(&mut rand::thread_rng())
+    }
+
+    fn sample_with<R>(&self, rng: &mut R) -> T
+    where
+        R: Rng + ?Sized,
+    {
+        self.observations[rng.gen_range(0..self.observations.len())].clone()
+    }
+
+    /// The expectation is computed exactly from the observations, so `sample_size` is
+    /// ignored.
+    fn expectation<Func>(&self, f: Func, _sample_size: usize) -> f64
+    where
+        Func: Fn(&T) -> f64,
+    {
+        self.observations.iter().map(f).sum::<f64>() / self.observations.len() as f64
+    }
+}
+
+impl<T> FiniteDistribution<T> for Empirical<T>
+where
+    T: Eq + Hash + Clone,
+{
+    fn table(&self) -> &HashMap<T, f64> {
+        &self.table
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+// [ Discrete Distributions ] ===========================================================
+
+// Struct: `Poisson` ====================================================================
+
+/// A Poisson distribution over counts with rate `lambda`.
+///
+/// Its support is infinite, so it does not implement [`FiniteDistribution`]; use
+/// [`Poisson::truncated_table`] for an approximate table.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Poisson {
+    lambda: f64,
+}
+
+impl Poisson {
+    /// Returns [`Error::InvalidParameter`] unless `lambda` is positive and finite.
+    pub fn new(lambda: f64) -> Result<Self> {
+        if !lambda.is_finite() || lambda <= 0.0 {
+            return Err(Error::InvalidParameter {
+                name: "lambda",
+                value: lambda,
+            });
+        }
+        Ok(Poisson { lambda })
+    }
+
+    pub fn lambda(&self) -> f64 {
+        self.lambda
+    }
+
+    /// Returns the probability mass function at each count from 0 to `max_k` inclusive.
+    /// The mass of counts above `max_k` is left out, so the probabilities sum to less
+    /// than 1.
+    pub fn truncated_table(&self, max_k: u64) -> HashMap<u64, f64> {
+        // Accumulate ln p(k) = -λ + k ln λ - ln k! so large rates do not underflow.
+        let mut log_p = -self.lambda;
+        let mut table = HashMap::with_capacity(max_k as usize + 1);
+        for k in 0..=max_k {
+            if k > 0 {
+                log_p += self.lambda.ln() - (k as f64).ln();
+            }
+            table.insert(k, log_p.exp());
+        }
+        table
+    }
+}
+
+impl Distribution<u64> for Poisson {
+    fn sample(&self) -> u64 {
+        self.sample_with(&mut rand::thread_rng())
+    }
+
+    /// Sample using Knuth's algorithm, which multiplies uniform draws until the product
+    /// falls below e^-λ. It takes O(λ) draws, so it suits small rates. Large rates are
+    /// split into chunks whose counts are summed, so that e^-λ does not underflow.
+    fn sample_with<R>(&self, rng: &mut R) -> u64
+    where
+        R: Rng + ?Sized,
+    {
+        const MAX_CHUNK: f64 = 500.0;
+
+        let mut remaining = self.lambda;
+        let mut total = 0;
+        while remaining > 0.0 {
+            let limit = (-remaining.min(MAX_CHUNK)).exp();
+            let mut product: f64 = rng.gen();
+            while product > limit {
+                total += 1;
+                product *= rng.gen::<f64>();
+            }
+            remaining -= MAX_CHUNK;
+        }
+        total
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+// Struct: `Geometric` ==================================================================
+
+/// A geometric distribution over the number of independent trials, each succeeding with
+/// probability `p`, up to and including the first success. Its support is 1, 2, 3, ….
+///
+/// Its support is infinite, so it does not implement [`FiniteDistribution`]; use
+/// [`Geometric::truncated_table`] for an approximate table.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Geometric {
+    p: f64,
+}
+
+impl Geometric {
+    /// Returns [`Error::InvalidProbability`] unless `p` lies in (0, 1].
+    pub fn new(p: f64) -> Result<Self> {
+        if !(p > 0.0 && p <= 1.0) {
+            return Err(Error::InvalidProbability(p));
+        }
+        Ok(Geometric { p })
+    }
+
+    /// Returns the probability of success on each trial.
+    pub fn p(&self) -> f64 {
+        self.p
+    }
+
+    /// Returns the probability mass function (1 - p)ᵏ⁻¹ p at each count from 1 to `max_k`
+    /// inclusive. The mass of counts above `max_k` is left out, so the probabilities sum
+    /// to less than 1.
+    pub fn truncated_table(&self, max_k: u64) -> HashMap<u64, f64> {
+        let mut table = HashMap::with_capacity(max_k as usize);
+        let mut mass = self.p;
+        for k in 1..=max_k {
+            table.insert(k, mass);
+            mass *= 1.0 - self.p;
+        }
+        table
+    }
+}
+
+impl Distribution<u64> for Geometric {
+    fn sample(&self) -> u64 {
+        self.sample_with(&mut rand::thread_rng())
+    }
+
+    /// Sample by inverse transform as ⌈ln(u) / ln(1 - p)⌉.
+    fn sample_with<R>(&self, rng: &mut R) -> u64
+    where
+        R: Rng + ?Sized,
+    {
+        if self.p == 1.0 {
+            return 1;
+        }
+        let u: f64 = rng.sample(Open01);
+        ((u.ln() / (-self.p).ln_1p()).ceil() as u64).max(1)
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+// [ Continuous Distributions ] =========================================================
+
+// Trait: `Density` =====================================================================
+
+/// A distribution whose density (or probability mass) can be evaluated.
+pub trait Density<T>: Distribution<T> {
+    /// Returns the natural logarithm of the density at `x`, which is `-∞` outside the
+    /// support.
+    fn log_density(&self, x: &T) -> f64;
+}
+
+// --------------------------------------------------------------------------------------
+
+// Trait: `InvCdfDistribution` ==========================================================
+
+/// A real-valued distribution that can be sampled by inverse transform sampling, as
+/// F⁻¹(U) for a uniform draw U from (0, 1).
+pub trait InvCdfDistribution: Distribution<f64> {
+    /// Returns the quantile F⁻¹(u) for `u` in (0, 1).
+    fn inverse_cdf(&self, u: f64) -> f64;
+
+    /// Return a Monte Carlo estimate of the expectation of f(X) from `sample_size`
+    /// evaluations of `f`, made in antithetic pairs F⁻¹(u) and F⁻¹(1 - u). When f ∘ F⁻¹
+    /// is monotone the two halves of a pair are negatively correlated, so the estimate
+    /// has lower variance than [`Distribution::expectation`] from as many samples.
+    ///
+    /// An odd `sample_size` is rounded up to a whole number of pairs.
+    fn expectation_antithetic<Func>(&self, f: Func, sample_size: usize) -> f64
+    where
+        Func: Fn(&f64) -> f64,
+    {
+        self.expectation_antithetic_with(f, sample_size, &mut rand::thread_rng())
+    }
+
+    /// Like [`InvCdfDistribution::expectation_antithetic`], drawing the uniforms using the
+    /// given random number generator.
+    fn expectation_antithetic_with<Func, R>(&self, f: Func, sample_size: usize, rng: &mut R) -> f64
+    where
+        Func: Fn(&f64) -> f64,
+        R: Rng + ?Sized,
+    {
+        let pairs = sample_size.div_ceil(2);
+        let sum: f64 = (0..pairs)
+            .map(|_| {
+                let u: f64 = rng.sample(Open01);
+                f(&self.inverse_cdf(u)) + f(&self.inverse_cdf(1.0 - u))
+            })
+            .sum();
+        sum / (2 * pairs) as f64
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+// Struct: `Gaussian` ===================================================================
+
+/// A normal distribution with mean `mu` and standard deviation `sigma`.
+///
+/// The default Monte Carlo [`Distribution::expectation`] is used, so an estimate of
+/// E[f(X)] from `n` samples has variance Var[f(X)] / n.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Gaussian {
+    mu: f64,
+    sigma: f64,
+}
+
+impl Gaussian {
+    /// Returns [`Error::InvalidParameter`] if `mu` is not finite or `sigma` is not
+    /// positive and finite.
+    pub fn new(mu: f64, sigma: f64) -> Result<Self> {
+        if !mu.is_finite() {
+            return Err(Error::InvalidParameter {
+                name: "mu",
+                value: mu,
+            });
+        }
+        if !sigma.is_finite() || sigma <= 0.0 {
+            return Err(Error::InvalidParameter {
+                name: "sigma",
+                value: sigma,
+            });
+        }
+        Ok(Gaussian { mu, sigma })
+    }
+
+    pub fn mu(&self) -> f64 {
+        self.mu
+    }
+
+    pub fn sigma(&self) -> f64 {
+        self.sigma
+    }
+}
+
+impl Distribution<f64> for Gaussian {
+    fn sample(&self) -> f64 {
+        self.sample_with(&mut rand::thread_rng())
+    }
+
+    /// Sample using the Box-Muller transform.
+    fn sample_with<R>(&self, rng: &mut R) -> f64
+    where
+        R: Rng + ?Sized,
+    {
+        // Draw from (0, 1] so that the logarithm is finite.
+        let u1: f64 = 1.0 - rng.gen::<f64>();
+        let u2: f64 = rng.gen();
+        let z = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+        self.mu + self.sigma * z
+    }
+}
+
+impl Density<f64> for Gaussian {
+    fn log_density(&self, x: &f64) -> f64 {
+        let z = (x - self.mu) / self.sigma;
+        -0.5 * z * z - self.sigma.ln() - 0.5 * (2.0 * std::f64::consts::PI).ln()
+    }
+}
+
+impl InvCdfDistribution for Gaussian {
+    fn inverse_cdf(&self, u: f64) -> f64 {
+        self.mu + self.sigma * standard_normal_quantile(u)
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+// Struct: `Uniform` ====================================================================
+
+/// A continuous uniform distribution over the interval [`low`, `high`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Uniform {
+    low: f64,
+    high: f64,
+}
+
+impl Uniform {
+    /// Returns [`Error::InvalidParameter`] unless `low` and `high` are finite and
+    /// `low < high`.
+    pub fn new(low: f64, high: f64) -> Result<Self> {
+        if !low.is_finite() {
+            return Err(Error::InvalidParameter {
+                name: "low",
+                value: low,
+            });
+        }
+        if !high.is_finite() || high <= low {
+            return Err(Error::InvalidParameter {
+                name: "high",
+                value: high,
+            });
+        }
+        Ok(Uniform { low, high })
+    }
+
+    pub fn low(&self) -> f64 {
+        self.low
+    }
+
+    pub fn high(&self) -> f64 {
+        self.high
+    }
+
+    /// Returns the exact mean (`low` + `high`) / 2, which
+    /// [`Distribution::expectation`] of the identity only estimates by sampling.
+    pub fn mean(&self) -> f64 {
+        (self.low + self.high) / 2.0
+    }
+}
+
+impl Distribution<f64> for Uniform {
+    fn sample(&self) -> f64 {
+        self.sample_with(&mut rand::thread_rng())
+    }
+
+    fn sample_with<R>(&self, rng: &mut R) -> f64
+    where
+        R: Rng + ?Sized,
+    {
+        let x = self.low + (self.high - self.low) * rng.gen::<f64>();
+        // Rounding can land exactly on `high`, which lies outside the interval.
+        if x < self.high {
+            x
+        } else {
+            self.low
+        }
+    }
+}
+
+impl Density<f64> for Uniform {
+    fn log_density(&self, x: &f64) -> f64 {
+        if (self.low..self.high).contains(x) {
+            -(self.high - self.low).ln()
+        } else {
+            f64::NEG_INFINITY
+        }
+    }
+}
+
+impl InvCdfDistribution for Uniform {
+    fn inverse_cdf(&self, u: f64) -> f64 {
+        self.low + (self.high - self.low) * u
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+// Struct: `Exponential` ================================================================
+
+/// An exponential distribution over [0, ∞) with rate `rate`, and so mean 1 / `rate`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Exponential {
+    rate: f64,
+}
+
+impl Exponential {
+    /// Returns [`Error::InvalidParameter`] unless `rate` is positive and finite.
+    pub fn new(rate: f64) -> Result<Self> {
+        if !rate.is_finite() || rate <= 0.0 {
+            return Err(Error::InvalidParameter {
+                name: "rate",
+                value: rate,
+            });
+        }
+        Ok(Exponential { rate })
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// Returns the exact mean 1 / `rate`, which [`Distribution::expectation`] of the
+    /// identity only estimates by sampling.
+    pub fn mean(&self) -> f64 {
+        1.0 / self.rate
+    }
+}
+
+impl Distribution<f64> for Exponential {
+    fn sample(&self) -> f64 {
+        self.sample_with(&mut rand::thread_rng())
+    }
+
+    /// Sample by inverse transform as -ln(u) / `rate`.
+    fn sample_with<R>(&self, rng: &mut R) -> f64
+    where
+        R: Rng + ?Sized,
+    {
+        let u: f64 = rng.sample(Open01);
+        -u.ln() / self.rate
+    }
+}
+
+impl Density<f64> for Exponential {
+    fn log_density(&self, x: &f64) -> f64 {
+        if *x >= 0.0 {
+            self.rate.ln() - self.rate * x
+        } else {
+            f64::NEG_INFINITY
+        }
+    }
+}
+
+impl InvCdfDistribution for Exponential {
+    fn inverse_cdf(&self, u: f64) -> f64 {
+        -(-u).ln_1p() / self.rate
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn gaussian_rejects_non_positive_sigma() {
+        assert!(Gaussian::new(0.0, 0.0).is_err());
+        assert!(Gaussian::new(0.0, -1.0).is_err());
+        assert!(Gaussian::new(0.0, f64::NAN).is_err());
+        assert!(Gaussian::new(f64::INFINITY, 1.0).is_err());
+        assert!(Gaussian::new(0.0, 1.0).is_ok());
+    }
+
+    #[test]
+    fn seeded_mixture_samples_are_reproducible() {
+        let components: Vec<(Box<dyn DynDistribution<f64>>, f64)> = vec![
+            (Box::new(Gaussian::new(-5.0, 1.0).unwrap()), 1.0),
+            (Box::new(Uniform::new(0.0, 1.0).unwrap()), 2.0),
+        ];
+        let mixture = Mixture::new(components).unwrap();
+        let draw = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..50)
+                .map(|_| mixture.sample_with(&mut rng))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(draw(3), draw(3));
+        assert_ne!(draw(3), draw(4));
+    }
+
+    #[test]
+    fn seeded_monte_carlo_estimates_are_reproducible() {
+        let gaussian = Gaussian::new(1.0, 2.0).unwrap();
+        let ci = |seed| {
+            gaussian.expectation_ci_with(|x| *x, 1_000, 0.95, &mut StdRng::seed_from_u64(seed))
+        };
+        assert_eq!(ci(11), ci(11));
+        let (estimate, lower, upper) = ci(11);
+        assert!(lower < estimate && estimate < upper);
+
+        let antithetic = |seed| {
+            gaussian.expectation_antithetic_with(|x| x * x, 1_000, &mut StdRng::seed_from_u64(seed))
+        };
+        assert_eq!(antithetic(12), antithetic(12));
+    }
+
+    #[test]
+    fn seeded_expectation_and_variance_share_draws() {
+        let gaussian = Gaussian::new(2.0, 3.0).unwrap();
+        let rng = || StdRng::seed_from_u64(17);
+        let estimate = gaussian.expectation_with(|x| *x, 500, &mut rng());
+        assert_eq!(estimate, gaussian.expectation_with(|x| *x, 500, &mut rng()));
+
+        let samples = gaussian.sample_n_with(500, &mut rng());
+        let mean = samples.iter().sum::<f64>() / 500.0;
+        let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / 499.0;
+        assert!((estimate - mean).abs() < 1e-9);
+        assert!((gaussian.variance_with(|x| *x, 500, &mut rng()) - variance).abs() < 1e-9);
+    }
+
+    #[test]
+    fn gaussian_sample_mean_converges_to_mu() {
+        let gaussian = Gaussian::new(3.0, 2.0).unwrap();
+        let mut rng = StdRng::seed_from_u64(5);
+        let n = 100_000;
+        let mean = (0..n).map(|_| gaussian.sample_with(&mut rng)).sum::<f64>() / n as f64;
+        assert!((mean - 3.0).abs() < 0.05, "mean = {mean}");
+    }
+
+    #[test]
+    fn seeded_samples_are_reproducible() {
+        let gaussian = Gaussian::new(0.0, 1.0).unwrap();
+        let draw = |seed| -> Vec<f64> {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..10).map(|_| gaussian.sample_with(&mut rng)).collect()
+        };
+        assert_eq!(draw(6), draw(6));
+        assert_ne!(draw(6), draw(7));
+    }
+
+    #[test]
+    fn and_then_samples_dependent_distributions() {
+        let mixture = Bernoulli::new(0.3)
+            .unwrap()
+            .and_then(|heads| Gaussian::new(if heads { 10.0 } else { 0.0 }, 1.0).unwrap());
+        let mut rng = StdRng::seed_from_u64(22);
+        let n = 50_000;
+        let mean = (0..n).map(|_| mixture.sample_with(&mut rng)).sum::<f64>() / n as f64;
+        // 0.3 * 10 + 0.7 * 0.
+        assert!((mean - 3.0).abs() < 0.05, "mean = {mean}");
+
+        let bounded = Constant::new(3)
+            .and_then(|n| Categorical::new((0..=n).map(|k| (k, 1.0)).collect()).unwrap());
+        assert!((0..1_000).all(|_| bounded.sample_with(&mut rng) <= 3));
+    }
+
+    #[test]
+    fn filter_keeps_only_matching_samples() {
+        let die = Categorical::new((1..=10).map(|x| (x, 1.0)).collect()).unwrap();
+        let even = die.clone().filter(|x| x % 2 == 0);
+        let mut rng = StdRng::seed_from_u64(23);
+        assert!((0..1_000).all(|_| even.sample_with(&mut rng) % 2 == 0));
+
+        let impossible = die.filter(|&x| x > 10);
+        assert_eq!(impossible.try_sample_with(100, &mut rng), None);
+    }
+
+    /// Returns a categorical distribution over 0..5 with weights 1..=5.
+    fn weighted() -> Categorical<u32> {
+        Categorical::new((0..5).map(|x| (x, (x + 1) as f64)).collect()).unwrap()
+    }
+
+    #[test]
+    fn zipped_table_is_the_product_of_marginals() {
+        let coin = Bernoulli::new(0.3).unwrap();
+        let joint = weighted().zip(coin.clone());
+        assert_eq!(joint.table().len(), 10);
+        for (x, &p) in weighted().table() {
+            for (y, &q) in coin.table() {
+                assert!((joint.probability(&(*x, *y)) - p * q).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn identically_seeded_iterators_agree() {
+        let draw = |seed| {
+            Gaussian::new(0.0, 1.0)
+                .unwrap()
+                .sample_iter_with(StdRng::seed_from_u64(seed))
+                .take_samples(100)
+        };
+        assert_eq!(draw(25).len(), 100);
+        assert_eq!(draw(25), draw(25));
+        assert_ne!(draw(25), draw(26));
+    }
+
+    #[test]
+    fn bernoulli_variance_matches_closed_form() {
+        let coin = Bernoulli::new(0.3).unwrap();
+        let indicator = |&heads: &bool| if heads { 1.0 } else { 0.0 };
+        let variance = coin.variance(indicator, 100_000);
+        assert!((variance - 0.21).abs() < 0.01, "variance = {variance}");
+        assert!((coin.std_dev(indicator, 100_000) - 0.21f64.sqrt()).abs() < 0.01);
+        assert!(coin.variance(indicator, 1).is_nan());
+        assert!(coin.variance(indicator, 0).is_nan());
+        let mut rng = StdRng::seed_from_u64(26);
+        assert!(coin.variance_with(indicator, 1, &mut rng).is_nan());
+    }
+
+    #[test]
+    fn confidence_interval_shrinks_like_inverse_square_root() {
+        let gaussian = Gaussian::new(0.0, 1.0).unwrap();
+        let width = |n| {
+            let rng = &mut StdRng::seed_from_u64(27);
+            let (_, lower, upper) = gaussian.expectation_ci_with(|x| *x, n, 0.95, rng);
+            upper - lower
+        };
+        assert!((width(10_000) - 2.0 * 1.96 / 100.0).abs() < 0.005);
+        let ratio = width(2_500) / width(40_000);
+        assert!((ratio - 4.0).abs() < 0.2, "ratio = {ratio}");
+    }
+
+    #[test]
+    fn finite_expectation_is_exact() {
+        let dist = weighted();
+        let mean = FiniteDistribution::expectation(&dist, |&x| x as f64);
+        assert!((mean - 40.0 / 15.0).abs() < 1e-12);
+        assert!((Distribution::expectation(&dist, |&x| x as f64, 1) - mean).abs() < 1e-12);
+    }
+
+    #[test]
+    fn entropy_and_kl_divergence() {
+        let coin = Bernoulli::new(0.5).unwrap();
+        assert!((coin.entropy() - 2f64.ln()).abs() < 1e-12);
+        assert_eq!(Constant::new(1).entropy(), 0.0);
+
+        let dist = weighted();
+        assert_eq!(kl_divergence(&dist, &dist), 0.0);
+        let narrower = Categorical::new(HashMap::from([(0, 1.0), (1, 1.0)])).unwrap();
+        assert!(kl_divergence(&narrower, &dist) > 0.0);
+        assert_eq!(kl_divergence(&dist, &narrower), f64::INFINITY);
+    }
+
+    #[test]
+    fn mode_and_arg_max_break_ties_towards_least_outcome() {
+        let uniform = || Categorical::new(('a'..='z').map(|x| (x, 1.0)).collect()).unwrap();
+        let (first, second) = (uniform(), uniform());
+        assert_eq!(first.mode(), Some(&'a'));
+        assert_eq!(first.mode(), second.mode());
+        assert_eq!(first.arg_max_by(|_| 0.0), Some(&'a'));
+        assert_eq!(
+            first.arg_max_by(|&x| if x == 'q' { 1.0 } else { 0.0 }),
+            Some(&'q')
+        );
+    }
+
+    #[test]
+    fn mode_of_unique_most_probable_outcome() {
+        assert_eq!(weighted().mode(), Some(&4));
+        assert_eq!(weighted().arg_max_by(|&x| -(x as f64)), Some(&0));
+    }
+
+    #[test]
+    fn sample_finite_frequencies_match_table() {
+        let dist = weighted();
+        let mut rng = StdRng::seed_from_u64(31);
+        let n = 100_000;
+        let mut counts = HashMap::new();
+        for _ in 0..n {
+            *counts.entry(sample_finite(&dist, &mut rng)).or_insert(0) += 1;
+        }
+
+        // Pearson's statistic has 4 degrees of freedom, and exceeds 18.5 with probability
+        // below 0.001.
+        let chi_squared: f64 = dist
+            .table()
+            .iter()
+            .map(|(x, &p)| {
+                let expected = p * n as f64;
+                let observed = counts.get(x).copied().unwrap_or(0) as f64;
+                (observed - expected).powi(2) / expected
+            })
+            .sum();
+        assert!(chi_squared < 18.5, "chi-squared = {chi_squared}");
+    }
+
+    #[test]
+    fn sample_finite_returns_last_outcome_when_mass_falls_short() {
+        struct Short(HashMap<u32, f64>);
+        impl Distribution<u32> for Short {
+            fn sample(&self) -> u32 {
+                sample_finite(self, &mut rand::thread_rng())
+            }
+        }
+        impl FiniteDistribution<u32> for Short {
+            fn table(&self) -> &HashMap<u32, f64> {
+                &self.0
+            }
+        }
+
+        let dist = Short(HashMap::from([(7, 0.0)]));
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(sample_finite(&dist, &mut rng), 7);
+    }
+
+    #[test]
+    fn seeded_sampling_is_reproducible_across_constructions() {
+        let draw = |dist: &Categorical<u32>| {
+            let mut rng = StdRng::seed_from_u64(7);
+            let samples: Vec<u32> = (0..100).map(|_| dist.sample_with(&mut rng)).collect();
+            let generic: Vec<u32> = (0..100).map(|_| sample_finite(dist, &mut rng)).collect();
+            (samples, generic)
+        };
+        assert_eq!(draw(&weighted()), draw(&weighted()));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn categorical_round_trips_through_json() {
+        let dist = Categorical::new(HashMap::from([('a', 0.25), ('b', 0.75)])).unwrap();
+        let json = serde_json::to_string(&dist).unwrap();
+        let restored: Categorical<char> = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.table(), dist.table());
+    }
+
+    #[test]
+    fn empirical_probability_is_relative_frequency() {
+        let empirical = Empirical::new(vec!['a', 'b', 'a', 'c', 'a', 'b']).unwrap();
+        assert_eq!(empirical.table().len(), 3);
+        assert!((empirical.probability(&'a') - 0.5).abs() < 1e-12);
+        assert!((empirical.probability(&'b') - 1.0 / 3.0).abs() < 1e-12);
+        assert!((empirical.probability(&'c') - 1.0 / 6.0).abs() < 1e-12);
+        assert_eq!(empirical.probability(&'d'), 0.0);
+        assert!(matches!(Empirical::<char>::new(vec![]), Err(Error::Empty)));
+    }
+
+    #[test]
+    fn even_mixture_of_constants_yields_each_value_half_the_time() {
+        let components: Vec<(Box<Constant<u32>>, f64)> = vec![
+            (Box::new(Constant::new(1)), 1.0),
+            (Box::new(Constant::new(2)), 1.0),
+        ];
+        let mixture = Mixture::new(components).unwrap();
+        assert_eq!(mixture.probability(&1), 0.5);
+        assert_eq!(mixture.probability(&2), 0.5);
+
+        let mut rng = StdRng::seed_from_u64(40);
+        let samples: Vec<u32> = (0..10_000).map(|_| mixture.sample_with(&mut rng)).collect();
+        let ones = samples.iter().filter(|&&x| x == 1).count() as f64 / 10_000.0;
+        assert!((ones - 0.5).abs() < 0.02, "ones = {ones}");
+        assert!(samples.iter().all(|&x| x == 1 || x == 2));
+    }
+
+    #[test]
+    fn choose_assigns_equal_probability_to_each_option() {
+        let choose = Choose::new(vec!["rock", "paper", "scissors"]).unwrap();
+        assert_eq!(choose.table().len(), 3);
+        for option in ["rock", "paper", "scissors"] {
+            assert!((choose.probability(&option) - 1.0 / 3.0).abs() < 1e-12);
+        }
+        assert!(matches!(Choose::<u32>::new(vec![]), Err(Error::Empty)));
+    }
+
+    #[test]
+    fn poisson_sample_mean_approaches_lambda() {
+        for lambda in [0.5, 4.0, 60.0] {
+            let poisson = Poisson::new(lambda).unwrap();
+            let mut rng = StdRng::seed_from_u64(42);
+            let total: u64 = (0..20_000).map(|_| poisson.sample_with(&mut rng)).sum();
+            let mean = total as f64 / 20_000.0;
+            // The standard error of the mean is sqrt(λ / n).
+            let tolerance = 4.0 * (lambda / 20_000.0).sqrt();
+            assert!(
+                (mean - lambda).abs() < tolerance,
+                "λ = {lambda}: mean = {mean}"
+            );
+        }
+    }
+
+    #[test]
+    fn uniform_samples_stay_in_range_around_the_midpoint() {
+        let uniform = Uniform::new(-1.0, 3.0).unwrap();
+        let mut rng = StdRng::seed_from_u64(43);
+        let samples: Vec<f64> = (0..10_000).map(|_| uniform.sample_with(&mut rng)).collect();
+        assert!(samples.iter().all(|&x| (-1.0..3.0).contains(&x)));
+        let mean = samples.iter().sum::<f64>() / 10_000.0;
+        assert!((mean - uniform.mean()).abs() < 0.05, "mean = {mean}");
+
+        assert_eq!(uniform.mean(), 1.0);
+        assert!(Uniform::new(1.0, 1.0).is_err());
+        assert!(Uniform::new(2.0, 1.0).is_err());
+    }
+
+    #[test]
+    fn total_variation_of_identical_and_disjoint_distributions() {
+        assert_eq!(total_variation(&weighted(), &weighted()), 0.0);
+
+        let low = Choose::new(vec![0, 1]).unwrap();
+        let high = Choose::new(vec![2, 3, 4]).unwrap();
+        assert!((total_variation(&low, &high) - 1.0).abs() < 1e-12);
+        assert!((total_variation(&high, &low) - 1.0).abs() < 1e-12);
+
+        // Half of the mass of `low` moves to 2.
+        let shifted = Choose::new(vec![0, 2]).unwrap();
+        assert!((total_variation(&low, &shifted) - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn pretty_table_is_sorted_by_probability_then_outcome() {
+        let dist = Categorical::new(HashMap::from([("b", 0.25), ("c", 0.5), ("a", 0.25)])).unwrap();
+        assert_eq!(dist.pretty(), "c: 0.5000\na: 0.2500\nb: 0.2500");
+    }
+
+    #[test]
+    fn antithetic_estimates_vary_less_for_monotone_functions() {
+        let gaussian = Gaussian::new(0.0, 1.0).unwrap();
+        let f = |x: &f64| x.exp();
+        let spread = |estimate: &dyn Fn(&mut StdRng) -> f64| {
+            let rng = &mut StdRng::seed_from_u64(59);
+            let estimates: Vec<f64> = (0..200).map(|_| estimate(rng)).collect();
+            let mean = estimates.iter().sum::<f64>() / 200.0;
+            estimates.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / 199.0
+        };
+        let plain =
+            spread(&|rng| (0..100).map(|_| f(&gaussian.sample_with(rng))).sum::<f64>() / 100.0);
+        let antithetic = spread(&|rng| gaussian.expectation_antithetic_with(f, 100, rng));
+        assert!(antithetic < plain, "{antithetic} vs {plain}");
+    }
+
+    #[test]
+    fn importance_sampling_recovers_the_target_mean() {
+        // The target is an unnormalized N(1, 1), sampled through a wider N(0, 2).
+        let target_log_density = |x: &f64| -0.5 * (x - 1.0).powi(2);
+        let proposal = Gaussian::new(0.0, 2.0).unwrap();
+        let rng = &mut StdRng::seed_from_u64(60);
+        let mean = importance_expectation_with(target_log_density, &proposal, |x| *x, 20_000, rng);
+        assert!((mean - 1.0).abs() < 0.05, "mean = {mean}");
+        let second_moment =
+            importance_expectation_with(target_log_density, &proposal, |x| x * x, 20_000, rng);
+        assert!((second_moment - 2.0).abs() < 0.1, "E[X²] = {second_moment}");
+    }
+
+    #[test]
+    fn geometric_sample_mean_approaches_inverse_p() {
+        for p in [0.2, 0.5, 1.0] {
+            let geometric = Geometric::new(p).unwrap();
+            let mut rng = StdRng::seed_from_u64(63);
+            let samples: Vec<u64> = (0..20_000)
+                .map(|_| geometric.sample_with(&mut rng))
+                .collect();
+            assert!(samples.iter().all(|&k| k >= 1));
+            let mean = samples.iter().sum::<u64>() as f64 / 20_000.0;
+            assert!((mean - 1.0 / p).abs() < 0.1, "p = {p}: mean = {mean}");
+        }
+        let table = Geometric::new(0.5).unwrap().truncated_table(3);
+        assert_eq!(table, HashMap::from([(1, 0.5), (2, 0.25), (3, 0.125)]));
+        for p in [0.0, -0.5, 1.5, f64::NAN] {
+            assert!(Geometric::new(p).is_err());
+        }
+    }
+
+    #[test]
+    fn binomial_table_sums_to_one_with_mean_np() {
+        for (n, p) in [(10, 0.3), (200, 0.75), (1_000, 0.01)] {
+            let binomial = Binomial::new(n, p).unwrap();
+            assert_eq!(binomial.table().len(), n as usize + 1);
+            let total: f64 = binomial.table().values().sum();
+            assert!((total - 1.0).abs() < 1e-9, "n = {n}: total = {total}");
+            let mean = FiniteDistribution::expectation(&binomial, |&k| k as f64);
+            assert!((mean - n as f64 * p).abs() < 1e-9, "n = {n}: mean = {mean}");
+        }
+    }
+
+    #[test]
+    fn marginalizing_a_product_recovers_its_factors() {
+        let coin = Bernoulli::new(0.3).unwrap();
+        let joint = weighted().zip(coin);
+        let first = marginalize(&joint, |&(x, _)| x).unwrap();
+        let second = marginalize(&joint, |&(_, heads)| heads).unwrap();
+        assert!(total_variation(&first, &weighted()) < 1e-12);
+        assert!((second.probability(&true) - 0.3).abs() < 1e-12);
+        assert!((second.probability(&false) - 0.7).abs() < 1e-12);
+    }
+
+    #[test]
+    fn exponential_samples_are_non_negative_with_mean_inverse_rate() {
+        let exponential = Exponential::new(2.5).unwrap();
+        let mut rng = StdRng::seed_from_u64(72);
+        let samples: Vec<f64> = (0..20_000)
+            .map(|_| exponential.sample_with(&mut rng))
+            .collect();
+        assert!(samples.iter().all(|&x| x >= 0.0));
+        let mean = samples.iter().sum::<f64>() / 20_000.0;
+        assert_eq!(exponential.mean(), 0.4);
+        assert!((mean - exponential.mean()).abs() < 0.01, "mean = {mean}");
+        for rate in [0.0, -1.0, f64::NAN] {
+            assert!(Exponential::new(rate).is_err());
+        }
+    }
+
+    #[test]
+    fn sampling_without_replacement_favours_likely_outcomes() {
+        let dist = weighted();
+        let rng = &mut StdRng::seed_from_u64(75);
+        let mut counts = [0; 5];
+        for _ in 0..5_000 {
+            let sample = dist.sample_without_replacement(2, rng).unwrap();
+            assert_eq!(sample.len(), 2);
+            assert_ne!(sample[0], sample[1]);
+            for x in sample {
+                counts[x as usize] += 1;
+            }
+        }
+        assert!(
+            counts.windows(2).all(|pair| pair[0] < pair[1]),
+            "{counts:?}"
+        );
+
+        assert_eq!(dist.sample_without_replacement(5, rng).unwrap().len(), 5);
+        let before = rng.clone();
+        assert!(matches!(
+            dist.sample_without_replacement(6, rng),
+            Err(Error::InvalidParameter { name: "k", .. })
+        ));
+        assert_eq!(*rng, before);
+    }
+
+    #[test]
+    fn heterogeneous_boxed_distributions_sample() {
+        let dists: Vec<Box<dyn DynDistribution<f64>>> = vec![
+            Box::new(Constant::new(3.0)),
+            Box::new(Uniform::new(10.0, 11.0).unwrap()),
+            Box::new(Gaussian::new(0.0, 1.0).unwrap()),
+        ];
+        let rng = &mut StdRng::seed_from_u64(76);
+        let samples: Vec<f64> = dists.iter().map(|dist| dist.sample_boxed(rng)).collect();
+        assert_eq!(samples[0], 3.0);
+        assert!((10.0..11.0).contains(&samples[1]));
+        assert!(samples[2].is_finite());
+        assert_eq!(dists[0].sample(), 3.0);
+    }
+
+    #[test]
+    fn sample_n_returns_exactly_n_samples() {
+        let dist = weighted();
+        for n in [0, 1, 17] {
+            assert_eq!(dist.sample_n(n).len(), n);
+            assert_eq!(
+                dist.sample_n_with(n, &mut StdRng::seed_from_u64(79)).len(),
+                n
+            );
+        }
+        // The distribution is still usable after sampling a batch.
+        assert!(dist.sample() < 5);
+    }
+
+    #[test]
+    fn cdf_is_non_decreasing_and_ends_at_one() {
+        let cdf = weighted().cdf_table();
+        let outcomes: Vec<u32> = cdf.iter().map(|&(x, _)| x).collect();
+        assert_eq!(outcomes, vec![0, 1, 2, 3, 4]);
+        assert!(cdf.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+        assert!((cdf[0].1 - 1.0 / 15.0).abs() < 1e-12);
+        assert!((cdf[4].1 - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn map_mut_numbers_samples_with_a_counting_closure() {
+        let mut count = 0;
+        let numbered = Constant::new('x').map_mut(move |x| {
+            count += 1;
+            (count, x)
+        });
+        let rng = &mut StdRng::seed_from_u64(87);
+        assert_eq!(numbered.sample_with(rng), (1, 'x'));
+        assert_eq!(numbered.sample(), (2, 'x'));
+        assert_eq!(numbered.sample_n_with(2, rng), vec![(3, 'x'), (4, 'x')]);
+    }
+
+    #[test]
+    fn conditioning_a_die_on_even_faces() {
+        let die = Choose::new((1..=6).collect()).unwrap();
+        let even = die.condition_on(|face| face % 2 == 0).unwrap();
+        assert_eq!(even.table().len(), 3);
+        for face in [2, 4, 6] {
+            assert!((even.probability(&face) - 1.0 / 3.0).abs() < 1e-12);
+        }
+        assert_eq!(even.probability(&1), 0.0);
+        assert!(matches!(
+            die.condition_on(|&face| face > 6),
+            Err(Error::Empty)
+        ));
+    }
+
+    #[test]
+    fn categorical_from_counts_normalizes_the_counts() {
+        let dist = Categorical::from_counts(HashMap::from([('a', 1), ('b', 3)])).unwrap();
+        assert_eq!(dist.probability(&'a'), 0.25);
+        assert_eq!(dist.probability(&'b'), 0.75);
+
+        assert_eq!(
+            Categorical::from_counts(HashMap::from([('a', 0), ('b', 0)])).err(),
+            Some(Error::InvalidWeights)
+        );
+        assert_eq!(
+            Categorical::<char>::from_counts(HashMap::new()).err(),
+            Some(Error::Empty)
+        );
+    }
+}
@@ -0,0 +1,969 @@
+use crate::convergence::{max_change, Convergence, ConvergenceReport};
+use crate::distribution::{Distribution, FiniteDistribution};
+use crate::error::{Error, Result};
+use crate::markov_decision_process::{
+    ActionValueFunction, FiniteMarkovDecisionProcess, MarkovDecisionProcess,
+};
+use crate::markov_process::{check_discount, NonTerminal, Reward, State, ValueFunction};
+use crate::policy::{DeterministicPolicy, Policy};
+use rand::Rng;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::hash::Hash;
+
+/// Upper bound on the number of improvement steps taken by [`policy_iteration`].
+const MAX_POLICY_ITERATIONS: usize = 10_000;
+
+/// Differences in action values smaller than this are treated as ties.
+const TIE_TOLERANCE: f64 = 1e-10;
+
+/// The value functions and policies for each number of steps remaining, as returned by
+/// [`finite_horizon_value_iteration`].
+type HorizonSolution<S, A> = (Vec<ValueFunction<S>>, Vec<DeterministicPolicy<S, A>>);
+
+// [ Helpers ] ==========================================================================
+
+/// Returns the expected value of r + γV(s') over the outcomes (s', r) of `step`, taking
+/// the value of terminal states to be zero.
+fn expected_return<S, X>(step: &X, vf: &ValueFunction<S>, gamma: f64) -> f64
+where
+    S: Ord + Hash,
+    X: FiniteDistribution<(State<S>, Reward)>,
+{
+    step.table()
+        .iter()
+        .map(|((next_state, reward), &p)| {
+            let next_value = match next_state {
+                State::NonTerminal(state) => vf.get(state).copied().unwrap_or(0.0),
+                State::Terminal(_) => 0.0,
+            };
+            p * (reward.0 + gamma * next_value)
+        })
+        .sum()
+}
+
+/// Returns the policy acting greedily with respect to `vf`. Where `current` is given, its
+/// action is kept unless another action is strictly better, so that ties cannot make
+/// policy iteration cycle. Other ties go to the least action, which
+/// [`MarkovDecisionProcess::actions`] lists first.
+fn greedy_policy<S, A, X>(
+    mdp: &FiniteMarkovDecisionProcess<S, A, X>,
+    vf: &ValueFunction<S>,
+    gamma: f64,
+    current: Option<&DeterministicPolicy<S, A>>,
+) -> DeterministicPolicy<S, A>
+where
+    S: Ord + Hash + Clone,
+    A: Ord + Hash + Clone,
+    X: FiniteDistribution<(State<S>, Reward)>,
+{
+    let mut action_for = HashMap::with_capacity(mdp.mapping().len());
+    for (state, actions) in mdp.mapping() {
+        let q = |action: &A| expected_return(&actions[action], vf, gamma);
+
+        let (best_action, best_value) = mdp
+            .actions(state)
+            .into_iter()
+            .map(|action| {
+                let value = q(&action);
+                (action, value)
+            })
+            .reduce(|best, next| if next.1 > best.1 { next } else { best })
+            .expect("every state has at least one action");
+        let current_action =
+            current
+                .and_then(|policy| policy.action_for(state))
+                .filter(|&action| {
+                    actions.contains_key(action) && q(action) >= best_value - TIE_TOLERANCE
+                });
+        let action = current_action.cloned().unwrap_or(best_action);
+        action_for.insert(state.state().clone(), action);
+    }
+    DeterministicPolicy::new(action_for)
+}
+
+// --------------------------------------------------------------------------------------
+
+// [ Bellman Operators ] ================================================================
+
+/// Returns the result of one synchronous sweep of the Bellman expectation operator for
+/// `policy` over every state of `mdp`:
+/// V(s) ← Σₐ π(a | s) Σ p(s', r | s, a) (r + γV(s')).
+///
+/// States missing from `vf` count as zero. Returns [`Error::InvalidPolicy`] if `policy`
+/// does not cover some state or picks an action that is not available in it.
+pub fn bellman_expectation_update<S, A, X, P>(
+    vf: &ValueFunction<S>,
+    mdp: &FiniteMarkovDecisionProcess<S, A, X>,
+    policy: &P,
+    gamma: f64,
+) -> Result<ValueFunction<S>>
+where
+    S: Ord + Hash + Clone,
+    A: Ord + Hash,
+    X: FiniteDistribution<(State<S>, Reward)>,
+    P: Policy<S, A>,
+    P::ActionDist: FiniteDistribution<A>,
+{
+    let mut next = HashMap::with_capacity(mdp.mapping().len());
+    for (state, actions) in mdp.mapping() {
+        let action_dist = policy.act(state).ok_or(Error::InvalidPolicy)?;
+        let mut value = 0.0;
+        for (action, &p) in action_dist.table() {
+            let step = actions.get(action).ok_or(Error::InvalidPolicy)?;
+            value += p * expected_return(step, vf, gamma);
+        }
+        next.insert(state.clone(), value);
+    }
+    Ok(next)
+}
+
+/// Returns the result of one synchronous sweep of the Bellman optimality operator over
+/// every state of `mdp`: V(s) ← maxₐ Σ p(s', r | s, a) (r + γV(s')).
+///
+/// States missing from `vf` count as zero.
+pub fn bellman_optimality_update<S, A, X>(
+    vf: &ValueFunction<S>,
+    mdp: &FiniteMarkovDecisionProcess<S, A, X>,
+    gamma: f64,
+) -> ValueFunction<S>
+where
+    S: Ord + Hash + Clone,
+    A: Ord + Hash,
+    X: FiniteDistribution<(State<S>, Reward)>,
+{
+    mdp.mapping()
+        .iter()
+        .map(|(state, actions)| {
+            let value = actions
+                .values()
+                .map(|step| expected_return(step, vf, gamma))
+                .fold(f64::NEG_INFINITY, f64::max);
+            (state.clone(), value)
+        })
+        .collect()
+}
+
+// --------------------------------------------------------------------------------------
+
+// [ Policy Iteration ] =================================================================
+
+/// Returns the policy acting greedily with respect to the value function `vf`.
+pub fn greedy_policy_from_vf<S, A, X>(
+    mdp: &FiniteMarkovDecisionProcess<S, A, X>,
+    vf: &ValueFunction<S>,
+    gamma: f64,
+) -> DeterministicPolicy<S, A>
+where
+    S: Ord + Hash + Clone,
+    A: Ord + Hash + Clone,
+    X: FiniteDistribution<(State<S>, Reward)>,
+{
+    greedy_policy(mdp, vf, gamma, None)
+}
+
+/// Returns an optimal policy of `mdp` for discount factor `gamma` and its value function.
+///
+/// Starting from an arbitrary deterministic policy, alternates exact evaluation of the
+/// current policy with greedy improvement until the policy stops changing. Returns
+/// [`Error::NotConverged`] if that takes more than an internal cap of iterations, and
+/// propagates [`Error::SingularMatrix`] if a policy cannot be evaluated. Returns
+/// [`Error::InvalidParameter`] unless `gamma` lies in [0, 1).
+pub fn policy_iteration<S, A, X>(
+    mdp: &FiniteMarkovDecisionProcess<S, A, X>,
+    gamma: f64,
+) -> Result<(DeterministicPolicy<S, A>, ValueFunction<S>)>
+where
+    S: Ord + Hash + Clone,
+    A: Ord + Hash + Clone,
+    X: FiniteDistribution<(State<S>, Reward)>,
+{
+    check_discount(gamma, false)?;
+    let zero = HashMap::new();
+    let mut policy = greedy_policy(mdp, &zero, gamma, None);
+    for _ in 0..MAX_POLICY_ITERATIONS {
+        let vf = mdp.apply_policy(&policy)?.value_function(gamma)?;
+        let improved = greedy_policy(mdp, &vf, gamma, Some(&policy));
+        if improved == policy {
+            return Ok((policy, vf));
+        }
+        policy = improved;
+    }
+    Err(Error::NotConverged {
+        iterations: MAX_POLICY_ITERATIONS,
+    })
+}
+
+// --------------------------------------------------------------------------------------
+
+// [ Value Iteration ] ==================================================================
+
+/// Returns an optimal policy of `mdp` for discount factor `gamma`, its value function, and
+/// a report of how the iteration stopped.
+///
+/// Starting from `initial`, or from V = 0 if it is `None`, repeatedly applies
+/// [`bellman_optimality_update`] until `convergence` stops it, then extracts the policy
+/// acting greedily with respect to V. States missing from `initial` start at zero, so a
+/// previous estimate for a slightly different process is a good warm start.
+///
+/// Returns [`Error::InvalidParameter`] unless `gamma` lies in [0, 1), since for γ = 1 the
+/// Bellman optimality operator need not be a contraction and the iteration need not
+/// converge. Use [`finite_horizon_value_iteration`] to solve an undiscounted problem over
+/// a fixed number of steps.
+pub fn value_iteration<S, A, X>(
+    mdp: &FiniteMarkovDecisionProcess<S, A, X>,
+    gamma: f64,
+    convergence: Convergence,
+    initial: Option<&ValueFunction<S>>,
+) -> Result<(
+    DeterministicPolicy<S, A>,
+    ValueFunction<S>,
+    ConvergenceReport,
+)>
+where
+    S: Ord + Hash + Clone,
+    A: Ord + Hash + Clone,
+    X: FiniteDistribution<(State<S>, Reward)>,
+{
+    check_discount(gamma, false)?;
+    let mut vf: ValueFunction<S> = mdp
+        .mapping()
+        .keys()
+        .map(|s| {
+            let value = initial.and_then(|vf| vf.get(s)).copied().unwrap_or(0.0);
+            (s.clone(), value)
+        })
+        .collect();
+    let mut iterations = 0;
+    let mut delta = f64::INFINITY;
+    while iterations < convergence.max_iters {
+        let next = bellman_optimality_update(&vf, mdp, gamma);
+        delta = max_change(&vf, &next);
+        vf = next;
+        iterations += 1;
+
+        if convergence.accepts(delta) {
+            break;
+        }
+    }
+
+    let report = convergence.report(iterations, delta);
+    Ok((greedy_policy_from_vf(mdp, &vf, gamma), vf, report))
+}
+
+/// Returns an optimal policy of `mdp` for discount factor `gamma`, its value function,
+/// and a report of how the iteration stopped, by Gauss-Seidel value iteration.
+///
+/// This is [`value_iteration`], except that each sweep updates the value function in
+/// place, so states later in a sweep already see the updated values of earlier ones. This
+/// typically needs fewer sweeps to converge. States are visited in the order given by
+/// `order`, or in the order of [`FiniteMarkovDecisionProcess::non_terminal_states`] if it
+/// is `None`.
+///
+/// Returns [`Error::InvalidParameter`] unless `gamma` lies in [0, 1), as for
+/// [`value_iteration`]. Panics if `order` does not list every state of `mdp` exactly once.
+pub fn value_iteration_in_place<S, A, X>(
+    mdp: &FiniteMarkovDecisionProcess<S, A, X>,
+    gamma: f64,
+    convergence: Convergence,
+    order: Option<&[NonTerminal<S>]>,
+) -> Result<(
+    DeterministicPolicy<S, A>,
+    ValueFunction<S>,
+    ConvergenceReport,
+)>
+where
+    S: Ord + Hash + Clone,
+    A: Ord + Hash + Clone,
+    X: FiniteDistribution<(State<S>, Reward)>,
+{
+    check_discount(gamma, false)?;
+    let order: Vec<&NonTerminal<S>> = match order {
+        Some(order) => order.iter().collect(),
+        None => mdp.non_terminal_states().iter().collect(),
+    };
+    let distinct: HashSet<&NonTerminal<S>> = order.iter().copied().collect();
+    assert!(
+        distinct.len() == order.len()
+            && order.len() == mdp.mapping().len()
+            && order.iter().all(|&s| mdp.mapping().contains_key(s)),
+        "order must list every state exactly once"
+    );
+
+    let mut vf: ValueFunction<S> = order.iter().map(|&s| (s.clone(), 0.0)).collect();
+    let mut sweeps = 0;
+    let mut delta = f64::INFINITY;
+    while sweeps < convergence.max_iters {
+        delta = 0.0;
+        for &state in &order {
+            let value = mdp.mapping()[state]
+                .values()
+                .map(|step| expected_return(step, &vf, gamma))
+                .fold(f64::NEG_INFINITY, f64::max);
+            let old = vf.insert(state.clone(), value).unwrap_or(0.0);
+            delta = delta.max((value - old).abs());
+        }
+        sweeps += 1;
+
+        if convergence.accepts(delta) {
+            break;
+        }
+    }
+
+    let report = convergence.report(sweeps, delta);
+    Ok((greedy_policy_from_vf(mdp, &vf, gamma), vf, report))
+}
+
+// --------------------------------------------------------------------------------------
+
+// [ Random Restarts ] ==================================================================
+
+/// The result of [`value_iteration_multistart`].
+#[derive(Clone, Debug)]
+pub struct MultistartSolution<S, A>
+where
+    S: Ord + Hash,
+{
+    /// The greedy policy with respect to `vf`.
+    pub policy: DeterministicPolicy<S, A>,
+    /// The value function of the restart with the smallest Bellman residual.
+    pub vf: ValueFunction<S>,
+    /// How the iteration stopped for the restart that gave `vf`.
+    pub report: ConvergenceReport,
+    /// The Bellman residual maxₛ |(T*V)(s) - V(s)| of `vf`.
+    pub residual: f64,
+    /// The variance of the value of each state across the restarts.
+    pub variance: ValueFunction<S>,
+}
+
+/// Runs [`value_iteration`] on `mdp` from `num_restarts` random initial value functions,
+/// each value drawn from `initial_dist` using `rng`, and returns the solution with the
+/// smallest Bellman residual.
+///
+/// For γ < 1 the Bellman optimality operator is a contraction, so every restart should
+/// converge to the same fixed point: a large
+/// [`variance`](MultistartSolution::variance) at any state shows that `convergence`
+/// stopped the iteration too early.
+///
+/// Returns [`Error::InvalidParameter`] unless `gamma` lies in [0, 1), as for
+/// [`value_iteration`]. Panics if `num_restarts` is zero.
+pub fn value_iteration_multistart<S, A, X, D, R>(
+    mdp: &FiniteMarkovDecisionProcess<S, A, X>,
+    gamma: f64,
+    convergence: Convergence,
+    num_restarts: usize,
+    initial_dist: &D,
+    rng: &mut R,
+) -> Result<MultistartSolution<S, A>>
+where
+    S: Ord + Hash + Clone,
+    A: Ord + Hash + Clone,
+    X: FiniteDistribution<(State<S>, Reward)>,
+    D: Distribution<f64>,
+    R: Rng + ?Sized,
+{
+    assert!(num_restarts > 0, "num_restarts must be positive");
+
+    let mut best: Option<MultistartSolution<S, A>> = None;
+    // The running mean and sum of squared deviations of each state's value, updated by
+    // Welford's method so that nearly equal values do not cancel catastrophically.
+    let mut moments: HashMap<NonTerminal<S>, (f64, f64)> =
+        HashMap::with_capacity(mdp.mapping().len());
+    for k in 1..=num_restarts {
+        let initial: ValueFunction<S> = mdp
+            .non_terminal_states()
+            .iter()
+            .map(|s| (s.clone(), initial_dist.sample_with(rng)))
+            .collect();
+        let (policy, vf, report) = value_iteration(mdp, gamma, convergence, Some(&initial))?;
+        let residual = max_change(&vf, &bellman_optimality_update(&vf, mdp, gamma));
+
+        for (state, &value) in &vf {
+            let (mean, m2) = moments.entry(state.clone()).or_insert((0.0, 0.0));
+            let delta = value - *mean;
+            *mean += delta / k as f64;
+            *m2 += delta * (value - *mean);
+        }
+        if best.as_ref().is_none_or(|b| residual < b.residual) {
+            best = Some(MultistartSolution {
+                policy,
+                vf,
+                report,
+                residual,
+                variance: HashMap::new(),
+            });
+        }
+    }
+
+    let n = num_restarts as f64;
+    let mut solution = best.expect("num_restarts is positive");
+    solution.variance = moments
+        .into_iter()
+        .map(|(state, (_, m2))| (state, m2 / n))
+        .collect();
+    Ok(solution)
+}
+
+// --------------------------------------------------------------------------------------
+
+// [ Prioritized Sweeping ] =============================================================
+
+/// Returns an optimal policy of `mdp` for discount factor `gamma`, its value function, and
+/// the number of single-state updates made, found by prioritized sweeping.
+///
+/// Instead of sweeping over every state, the state with the largest Bellman error
+/// |maxₐ Σ p(s', r | s, a) (r + γV(s')) - V(s)| is updated first, and only the
+/// predecessors of an updated state have their errors recomputed. States are queued while
+/// their error exceeds `theta`, and the search stops once no state does. This makes far
+/// fewer updates than [`value_iteration`] when each state has few predecessors.
+///
+/// Returns [`Error::InvalidParameter`] unless `gamma` lies in [0, 1) and `theta` is
+/// positive, as otherwise the queue need never empty, and [`Error::NotConverged`] if it
+/// still holds a state after `max_updates` updates.
+pub fn prioritized_value_iteration<S, A, X>(
+    mdp: &FiniteMarkovDecisionProcess<S, A, X>,
+    gamma: f64,
+    theta: f64,
+    max_updates: usize,
+) -> Result<(DeterministicPolicy<S, A>, ValueFunction<S>, usize)>
+where
+    S: Ord + Hash + Clone,
+    A: Ord + Hash + Clone,
+    X: FiniteDistribution<(State<S>, Reward)>,
+{
+    check_discount(gamma, false)?;
+    if theta.is_nan() || theta <= 0.0 {
+        return Err(Error::InvalidParameter {
+            name: "theta",
+            value: theta,
+        });
+    }
+    let index = mdp.state_index();
+    let states = index.states();
+
+    let mut predecessors: Vec<HashSet<usize>> = vec![HashSet::new(); states.len()];
+    for (i, state) in states.iter().enumerate() {
+        for step in mdp.mapping()[state].values() {
+            for ((next_state, _), &p) in step.table() {
+                if let State::NonTerminal(next_state) = next_state {
+                    if p > 0.0 {
+                        if let Some(j) = index.index_of(next_state) {
+                            predecessors[j].insert(i);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let mut vf: ValueFunction<S> = states.iter().map(|s| (s.clone(), 0.0)).collect();
+    let backup = |vf: &ValueFunction<S>, i: usize| {
+        mdp.mapping()[&states[i]]
+            .values()
+            .map(|step| expected_return(step, vf, gamma))
+            .fold(f64::NEG_INFINITY, f64::max)
+    };
+
+    // Non-negative floats order the same way as their bit patterns, which are `Ord`.
+    // Entries whose priority no longer matches `priority` are stale and skipped.
+    let mut priority = vec![0.0; states.len()];
+    let mut queue = BinaryHeap::new();
+    for i in 0..states.len() {
+        let error = (backup(&vf, i) - vf[&states[i]]).abs();
+        if error > theta {
+            priority[i] = error;
+            queue.push((error.to_bits(), i));
+        }
+    }
+
+    let mut updates = 0;
+    while let Some((bits, i)) = queue.pop() {
+        if f64::from_bits(bits) != priority[i] {
+            continue;
+        }
+        if updates == max_updates {
+            return Err(Error::NotConverged {
+                iterations: max_updates,
+            });
+        }
+        let value = backup(&vf, i);
+        vf.insert(states[i].clone(), value);
+        priority[i] = 0.0;
+        updates += 1;
+
+        for &j in &predecessors[i] {
+            let error = (backup(&vf, j) - vf[&states[j]]).abs();
+            if error > theta && error > priority[j] {
+                priority[j] = error;
+                queue.push((error.to_bits(), j));
+            }
+        }
+    }
+
+    Ok((greedy_policy_from_vf(mdp, &vf, gamma), vf, updates))
+}
+
+// --------------------------------------------------------------------------------------
+
+// [ Finite Horizon ] ===================================================================
+
+/// Returns the optimal value functions and policies of `mdp` over a finite horizon of
+/// `horizon` steps, found by backward induction.
+///
+/// Returns `horizon + 1` value functions, where `vfs[k]` is the optimal value with `k`
+/// steps remaining: `vfs[0]` is zero everywhere, and each later one is the
+/// [`bellman_optimality_update`] of the one before. Also returns `horizon` policies, where
+/// `policies[k]` is optimal with `k + 1` steps remaining, acting greedily with respect to
+/// `vfs[k]`. An episode starting with `horizon` steps to go follows
+/// `policies[horizon - 1 - t]` at time step t.
+///
+/// Unlike the infinite-horizon solvers, this accepts the undiscounted case `gamma = 1`,
+/// since the return over a bounded number of steps is always finite. Returns
+/// [`Error::InvalidParameter`] unless `gamma` lies in [0, 1].
+pub fn finite_horizon_value_iteration<S, A, X>(
+    mdp: &FiniteMarkovDecisionProcess<S, A, X>,
+    gamma: f64,
+    horizon: usize,
+) -> Result<HorizonSolution<S, A>>
+where
+    S: Ord + Hash + Clone,
+    A: Ord + Hash + Clone,
+    X: FiniteDistribution<(State<S>, Reward)>,
+{
+    check_discount(gamma, true)?;
+    let mut vfs: Vec<ValueFunction<S>> = Vec::with_capacity(horizon + 1);
+    let mut policies = Vec::with_capacity(horizon);
+    vfs.push(mdp.mapping().keys().map(|s| (s.clone(), 0.0)).collect());
+    for k in 0..horizon {
+        policies.push(greedy_policy(mdp, &vfs[k], gamma, None));
+        vfs.push(bellman_optimality_update(&vfs[k], mdp, gamma));
+    }
+    Ok((vfs, policies))
+}
+
+// --------------------------------------------------------------------------------------
+
+// [ Action Values ] ====================================================================
+
+/// Returns the action-value function Q(s, a) = Σ p(s', r | s, a) (r + γV(s')) of following
+/// `policy` in `mdp`, where V is the value function of `policy`.
+///
+/// Propagates the errors of [`FiniteMarkovDecisionProcess::apply_policy`] and of the
+/// exact value function solve.
+pub fn action_value_function<S, A, X, P>(
+    mdp: &FiniteMarkovDecisionProcess<S, A, X>,
+    policy: &P,
+    gamma: f64,
+) -> Result<ActionValueFunction<S, A>>
+where
+    S: Ord + Hash + Clone,
+    A: Ord + Hash + Clone,
+    X: FiniteDistribution<(State<S>, Reward)>,
+    P: Policy<S, A>,
+    P::ActionDist: FiniteDistribution<A>,
+{
+    let vf = mdp.apply_policy(policy)?.value_function(gamma)?;
+    let mut q = HashMap::new();
+    for (state, actions) in mdp.mapping() {
+        for (action, step) in actions {
+            let value = expected_return(step, &vf, gamma);
+            q.insert((state.clone(), action.clone()), value);
+        }
+    }
+    Ok(q)
+}
+
+// --------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distribution::{Categorical, Constant};
+    use crate::markov_process::{NonTerminal, Terminal};
+    use crate::policy::StochasticPolicy;
+
+    type Cell = (i32, i32);
+
+    /// A grid of two rows and three columns with the goal, worth 10, in the top-right
+    /// corner and a pit, worth -10, below it, separated from the bottom-left cell by a
+    /// wall. Every move costs 1, and moving into the wall or off the grid stays put.
+    fn two_rows() -> FiniteMarkovDecisionProcess<Cell, char, Constant<(State<Cell>, Reward)>> {
+        let terminals = HashMap::from([((0, 2), 10.0), ((1, 2), -10.0)]);
+        let cells = [(0, 0), (0, 1), (1, 0)];
+        let moves = [('U', (-1, 0)), ('D', (1, 0)), ('L', (0, -1)), ('R', (0, 1))];
+        let mapping = cells
+            .iter()
+            .map(|&(row, col)| {
+                let actions = moves
+                    .iter()
+                    .map(|&(action, (dr, dc))| {
+                        let next = (row + dr, col + dc);
+                        let outcome = if let Some(&reward) = terminals.get(&next) {
+                            (State::Terminal(Terminal::new(next)), Reward(reward - 1.0))
+                        } else if cells.contains(&next) {
+                            (State::NonTerminal(NonTerminal::new(next)), Reward(-1.0))
+                        } else {
+                            let here = (row, col);
+                            (State::NonTerminal(NonTerminal::new(here)), Reward(-1.0))
+                        };
+                        (action, Constant::new(outcome))
+                    })
+                    .collect();
+                ((row, col), actions)
+            })
+            .collect();
+        FiniteMarkovDecisionProcess::new(mapping).unwrap()
+    }
+
+    #[test]
+    fn policy_iteration_finds_shortest_path_to_goal() {
+        let mdp = two_rows();
+        let (policy, vf) = policy_iteration(&mdp, 0.9).unwrap();
+        let expected = [
+            ((0, 0), 'R', -1.0 + 0.9 * 9.0),
+            ((0, 1), 'R', 9.0),
+            ((1, 0), 'U', -1.0 + 0.9 * (-1.0 + 0.9 * 9.0)),
+        ];
+        assert_eq!(vf.len(), expected.len());
+        for (cell, action, value) in expected {
+            let state = NonTerminal::new(cell);
+            assert_eq!(policy.action_for(&state), Some(&action));
+            assert!((vf[&state] - value).abs() < 1e-9);
+        }
+    }
+    #[test]
+    fn value_iteration_matches_policy_iteration() {
+        let mdp = two_rows();
+        let (expected_policy, optimal) = policy_iteration(&mdp, 0.9).unwrap();
+        let (policy, vf, _) =
+            value_iteration(&mdp, 0.9, Convergence::new(1e-12, 10_000), None).unwrap();
+        assert_eq!(policy, expected_policy);
+        for (state, value) in &optimal {
+            assert!((vf[state] - value).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn value_iteration_gap_shrinks_monotonically() {
+        let mdp = two_rows();
+        let (_, optimal) = policy_iteration(&mdp, 0.9).unwrap();
+        let gap = |max_iters| {
+            let (_, vf, _) =
+                value_iteration(&mdp, 0.9, Convergence::new(0.0, max_iters), None).unwrap();
+            optimal
+                .iter()
+                .map(|(state, value)| (vf[state] - value).abs())
+                .fold(0.0, f64::max)
+        };
+        let gaps: Vec<f64> = (0..10).map(gap).collect();
+        assert!(gaps[0] > 0.0);
+        assert!(gaps.windows(2).all(|pair| pair[1] <= pair[0]));
+    }
+
+    #[test]
+    fn action_values_average_to_state_values() {
+        let mdp = two_rows();
+        let actions = ['U', 'D', 'L', 'R'];
+        let uniform = Categorical::new(actions.iter().map(|&a| (a, 1.0)).collect()).unwrap();
+        let policy = StochasticPolicy::new(
+            mdp.mapping()
+                .keys()
+                .map(|state| (*state.state(), uniform.clone()))
+                .collect(),
+        );
+        let q = action_value_function(&mdp, &policy, 0.9).unwrap();
+        let vf = mdp
+            .apply_policy(&policy)
+            .unwrap()
+            .value_function(0.9)
+            .unwrap();
+        for state in mdp.mapping().keys() {
+            let expected: f64 = actions
+                .iter()
+                .map(|&action| uniform.probability(&action) * q[&(*state, action)])
+                .sum();
+            assert!((vf[state] - expected).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn greedy_policy_breaks_ties_towards_the_first_action() {
+        // With V = 0 every move from the top-left cell is worth -1, so all actions tie.
+        let mdp = two_rows();
+        let policy = greedy_policy_from_vf(&mdp, &HashMap::new(), 0.9);
+        assert_eq!(policy.action_for(&NonTerminal::new((0, 0))), Some(&'D'));
+    }
+
+    #[test]
+    fn bellman_updates_match_a_hand_computed_sweep() {
+        use crate::markov_process::Terminal;
+
+        // In 'a', "stay" earns 1 and stays, while "go" moves to 'b' for nothing or ends
+        // with reward 2, each with probability 1/2. In 'b', "stay" earns 3 and stays.
+        let outcomes = |pairs: Vec<(State<char>, f64, f64)>| {
+            Categorical::new(
+                pairs
+                    .into_iter()
+                    .map(|(next_state, reward, p)| ((next_state, Reward(reward)), p))
+                    .collect(),
+            )
+            .unwrap()
+        };
+        let a = State::NonTerminal(NonTerminal::new('a'));
+        let b = State::NonTerminal(NonTerminal::new('b'));
+        let end = State::Terminal(Terminal::new('t'));
+        let mdp = FiniteMarkovDecisionProcess::new(HashMap::from([
+            (
+                'a',
+                HashMap::from([
+                    ("stay", outcomes(vec![(a, 1.0, 1.0)])),
+                    ("go", outcomes(vec![(b, 0.0, 0.5), (end, 2.0, 0.5)])),
+                ]),
+            ),
+            (
+                'b',
+                HashMap::from([("stay", outcomes(vec![(b, 3.0, 1.0)]))]),
+            ),
+        ]))
+        .unwrap();
+        let vf = HashMap::from([(NonTerminal::new('a'), 0.0), (NonTerminal::new('b'), 4.0)]);
+
+        // a: max(1 + 0.5 · 0, 0.5 (0 + 0.5 · 4) + 0.5 · 2) = 2, b: 3 + 0.5 · 4 = 5.
+        let optimal = bellman_optimality_update(&vf, &mdp, 0.5);
+        assert_eq!(optimal[&NonTerminal::new('a')], 2.0);
+        assert_eq!(optimal[&NonTerminal::new('b')], 5.0);
+
+        let stay = DeterministicPolicy::new(HashMap::from([('a', "stay"), ('b', "stay")]));
+        let expected = bellman_expectation_update(&vf, &mdp, &stay, 0.5).unwrap();
+        assert_eq!(expected[&NonTerminal::new('a')], 1.0);
+        assert_eq!(expected[&NonTerminal::new('b')], 5.0);
+    }
+
+    /// A corridor of `len` cells ending in a goal, where moving right from the last cell
+    /// reaches the goal for a reward of 9, and every other move costs 1. Moving left from
+    /// the first cell stays put.
+    fn chain(len: i32) -> FiniteMarkovDecisionProcess<i32, char, Constant<(State<i32>, Reward)>> {
+        let mapping = (0..len)
+            .map(|cell| {
+                let left = State::NonTerminal(NonTerminal::new((cell - 1).max(0)));
+                let right = if cell + 1 == len {
+                    (State::Terminal(Terminal::new(len)), Reward(9.0))
+                } else {
+                    (State::NonTerminal(NonTerminal::new(cell + 1)), Reward(-1.0))
+                };
+                let actions = HashMap::from([
+                    ('L', Constant::new((left, Reward(-1.0)))),
+                    ('R', Constant::new(right)),
+                ]);
+                (cell, actions)
+            })
+            .collect();
+        FiniteMarkovDecisionProcess::new(mapping).unwrap()
+    }
+
+    #[test]
+    fn prioritized_value_iteration_matches_value_iteration() {
+        let mdp = two_rows();
+        let (policy, vf, _) = prioritized_value_iteration(&mdp, 0.9, 1e-10, 1_000).unwrap();
+        let (expected_policy, expected, _) =
+            value_iteration(&mdp, 0.9, Convergence::new(1e-12, 10_000), None).unwrap();
+        assert_eq!(policy, expected_policy);
+        for (state, value) in &expected {
+            assert!((vf[state] - value).abs() < 1e-8);
+        }
+    }
+
+    #[test]
+    fn prioritized_value_iteration_rejects_invalid_parameters() {
+        let mdp = two_rows();
+        for (gamma, theta, name) in [(1.0, 1e-6, "gamma"), (0.9, 0.0, "theta")] {
+            assert!(matches!(
+                prioritized_value_iteration(&mdp, gamma, theta, 1_000),
+                Err(Error::InvalidParameter { name: n, .. }) if n == name
+            ));
+        }
+        assert!(matches!(
+            prioritized_value_iteration(&mdp, 0.9, f64::NAN, 1_000),
+            Err(Error::InvalidParameter { name: "theta", .. })
+        ));
+    }
+
+    #[test]
+    fn prioritized_value_iteration_stops_after_max_updates() {
+        let mdp = two_rows();
+        assert!(matches!(
+            prioritized_value_iteration(&mdp, 0.9, 1e-10, 1),
+            Err(Error::NotConverged { iterations: 1 })
+        ));
+    }
+
+    #[test]
+    fn prioritized_value_iteration_makes_fewer_updates_on_a_chain() {
+        let mdp = chain(12);
+        let (_, vf, updates) = prioritized_value_iteration(&mdp, 0.9, 1e-10, 10_000).unwrap();
+
+        let (_, expected, report) =
+            value_iteration(&mdp, 0.9, Convergence::new(1e-10, 10_000), None).unwrap();
+        for (state, value) in &expected {
+            assert!((vf[state] - value).abs() < 1e-8);
+        }
+        let sweep_updates = report.iterations * mdp.mapping().len();
+        assert!(updates < sweep_updates, "{updates} vs {sweep_updates}");
+    }
+
+    #[test]
+    fn in_place_value_iteration_needs_fewer_sweeps_from_the_goal_backwards() {
+        let mdp = chain(12);
+        let convergence = Convergence::new(1e-10, 10_000);
+        let (_, expected, synchronous) = value_iteration(&mdp, 0.9, convergence, None).unwrap();
+        let synchronous = synchronous.iterations;
+
+        let mut order: Vec<NonTerminal<i32>> = mdp.mapping().keys().cloned().collect();
+        order.sort_by_key(|state| std::cmp::Reverse(*state.state()));
+        let (_, vf, report) =
+            value_iteration_in_place(&mdp, 0.9, convergence, Some(&order)).unwrap();
+        let in_place = report.iterations;
+        for (state, value) in &expected {
+            assert!((vf[state] - value).abs() < 1e-8);
+        }
+        assert!(
+            in_place * 2 < synchronous,
+            "{in_place} vs {synchronous} sweeps"
+        );
+    }
+
+    #[test]
+    fn report_flags_hitting_the_iteration_limit() {
+        let mdp = two_rows();
+        let (_, _, report) = value_iteration(&mdp, 0.9, Convergence::new(1e-12, 1), None).unwrap();
+        assert_eq!(report.iterations, 1);
+        assert!(!report.converged);
+        assert!(report.final_delta > 1e-12);
+
+        let (_, _, report) =
+            value_iteration(&mdp, 0.9, Convergence::new(1e-12, 10_000), None).unwrap();
+        assert!(report.converged);
+        assert!(report.iterations < 10_000);
+        assert!(report.final_delta < 1e-12);
+    }
+
+    #[test]
+    fn finite_horizon_solution_of_a_two_step_problem() {
+        use crate::distribution::Constant;
+        use crate::markov_process::Terminal;
+
+        // In 'a', "cash" ends with reward 1 and "grow" moves to 'b' for nothing; in 'b',
+        // "cash" ends with reward 3. Growing only pays with two steps to go.
+        let end = |reward| Constant::new((State::Terminal(Terminal::new('t')), Reward(reward)));
+        let grow = Constant::new((State::NonTerminal(NonTerminal::new('b')), Reward(0.0)));
+        let mdp = FiniteMarkovDecisionProcess::new(HashMap::from([
+            ('a', HashMap::from([("cash", end(1.0)), ("grow", grow)])),
+            ('b', HashMap::from([("cash", end(3.0))])),
+        ]))
+        .unwrap();
+
+        let (vfs, policies) = finite_horizon_value_iteration(&mdp, 1.0, 2).unwrap();
+        let (a, b) = (NonTerminal::new('a'), NonTerminal::new('b'));
+        assert_eq!(vfs.len(), 3);
+        assert_eq!((vfs[0][&a], vfs[0][&b]), (0.0, 0.0));
+        assert_eq!((vfs[1][&a], vfs[1][&b]), (1.0, 3.0));
+        assert_eq!((vfs[2][&a], vfs[2][&b]), (3.0, 3.0));
+        assert_eq!(policies.len(), 2);
+        assert_eq!(policies[0].action_for(&a), Some(&"cash"));
+        assert_eq!(policies[1].action_for(&a), Some(&"grow"));
+        assert_eq!(policies[1].action_for(&b), Some(&"cash"));
+
+        assert!(finite_horizon_value_iteration(&mdp, 1.5, 2).is_err());
+    }
+
+    #[test]
+    fn warm_start_from_the_solution_converges_in_one_sweep() {
+        let mdp = two_rows();
+        let convergence = Convergence::new(1e-9, 10_000);
+        let (_, exact, cold) = value_iteration(&mdp, 0.9, convergence, None).unwrap();
+        assert!(cold.iterations > 1);
+
+        let (_, vf, warm) = value_iteration(&mdp, 0.9, convergence, Some(&exact)).unwrap();
+        assert_eq!(warm.iterations, 1);
+        assert!(warm.converged);
+        assert!(max_change(&vf, &exact) < 1e-9);
+
+        // States missing from the warm start begin at zero.
+        let (_, vf, _) = value_iteration(&mdp, 0.9, convergence, Some(&HashMap::new())).unwrap();
+        assert!(max_change(&vf, &exact) < 1e-8);
+    }
+
+    #[test]
+    fn multistart_restarts_agree_on_the_fixed_point() {
+        use crate::distribution::Uniform;
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let mdp = two_rows();
+        let convergence = Convergence::new(1e-10, 10_000);
+        let initial = Uniform::new(-100.0, 100.0).unwrap();
+        let solve = |seed| {
+            let rng = &mut StdRng::seed_from_u64(seed);
+            value_iteration_multistart(&mdp, 0.9, convergence, 8, &initial, rng).unwrap()
+        };
+        let solution = solve(92);
+        let (_, exact, _) = value_iteration(&mdp, 0.9, convergence, None).unwrap();
+        assert!(max_change(&solution.vf, &exact) < 1e-8);
+        assert!(solution.residual < 1e-9);
+        assert_eq!(solution.variance.len(), exact.len());
+        assert!(solution.variance.values().all(|&v| v < 1e-12));
+        assert_eq!(solve(92).vf, solution.vf);
+
+        // Stopped after a single sweep, the restarts still disagree.
+        let rng = &mut StdRng::seed_from_u64(92);
+        let early =
+            value_iteration_multistart(&mdp, 0.9, Convergence::new(0.0, 1), 8, &initial, rng)
+                .unwrap();
+        assert!(early.variance.values().all(|&v| v > 1.0));
+    }
+
+    #[test]
+    fn in_place_value_iteration_matches_value_iteration() {
+        let mdp = chain(4);
+        let convergence = Convergence::new(1e-12, 10_000);
+        let (_, expected, _) = value_iteration(&mdp, 0.9, convergence, None).unwrap();
+        let (_, vf, report) = value_iteration_in_place(&mdp, 0.9, convergence, None).unwrap();
+        assert!(report.converged);
+        for (state, value) in &expected {
+            assert!((vf[state] - value).abs() < 1e-8);
+        }
+    }
+
+    #[test]
+    fn in_place_value_iteration_rejects_invalid_discount() {
+        let mdp = chain(4);
+        for gamma in [1.0, -0.1, f64::NAN] {
+            let result = value_iteration_in_place(&mdp, gamma, Convergence::new(1e-6, 100), None);
+            assert!(matches!(
+                result,
+                Err(Error::InvalidParameter { name: "gamma", .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn undiscounted_solves_need_a_finite_horizon() {
+        let mdp = two_rows();
+        let is_gamma_error =
+            |error: Error| matches!(error, Error::InvalidParameter { name: "gamma", .. });
+        for gamma in [1.0, 1.5, -0.1] {
+            let convergence = Convergence::new(1e-6, 100);
+            assert!(is_gamma_error(policy_iteration(&mdp, gamma).unwrap_err()));
+            assert!(is_gamma_error(
+                value_iteration(&mdp, gamma, convergence, None).unwrap_err()
+            ));
+        }
+
+        let (vfs, _) = finite_horizon_value_iteration(&mdp, 1.0, 3).unwrap();
+        // Undiscounted, the goal is worth 10 less 1 for each move, including the last.
+        assert_eq!(vfs[3][&NonTerminal::new((0, 1))], 9.0);
+        assert_eq!(vfs[3][&NonTerminal::new((0, 0))], 8.0);
+        assert!(is_gamma_error(
+            finite_horizon_value_iteration(&mdp, 1.5, 3).unwrap_err()
+        ));
+    }
+}
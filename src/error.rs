@@ -0,0 +1,65 @@
+use std::fmt;
+
+// Enum: `Error` ========================================================================
+
+/// Errors raised when constructing or working with distributions and processes.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    /// The weights given for a distribution were negative, not finite, or summed to zero.
+    InvalidWeights,
+    /// A distribution or summary statistic was computed from an empty collection.
+    Empty,
+    /// A probability parameter was outside of the interval [0, 1].
+    InvalidProbability(f64),
+    /// A parameter of a distribution was outside of its valid range.
+    InvalidParameter { name: &'static str, value: f64 },
+    /// A successor state is marked terminal but is a non-terminal state of the process, or
+    /// is marked non-terminal but is not one.
+    InconsistentTerminal,
+    /// A linear system could not be solved because its matrix is singular.
+    SingularMatrix,
+    /// A non-terminal state of a decision process has no actions available.
+    NoActions,
+    /// A policy does not cover some state, or picks an action that is not available.
+    InvalidPolicy,
+    /// An iterative algorithm did not converge within its iteration limit.
+    NotConverged { iterations: usize },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidWeights => {
+                write!(f, "weights must be non-negative, finite and not all zero")
+            }
+            Error::Empty => write!(f, "collection is empty"),
+            Error::InvalidProbability(p) => {
+                write!(f, "probability {} is outside of the interval [0, 1]", p)
+            }
+            Error::InvalidParameter { name, value } => {
+                write!(f, "invalid value {} for parameter `{}`", value, name)
+            }
+            Error::InconsistentTerminal => {
+                write!(f, "successor state is tagged inconsistently")
+            }
+            Error::SingularMatrix => write!(f, "matrix is singular"),
+            Error::NoActions => write!(f, "non-terminal state has no actions"),
+            Error::InvalidPolicy => {
+                write!(
+                    f,
+                    "policy does not cover a state or picks an unknown action"
+                )
+            }
+            Error::NotConverged { iterations } => {
+                write!(f, "did not converge after {} iterations", iterations)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A specialized `Result` type for this crate.
+pub type Result<T> = std::result::Result<T, Error>;
+
+// --------------------------------------------------------------------------------------
@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+// Trait: `ValueFunctionApprox` =========================================================
+
+/// An approximation of a value function over states of type `S` that can be improved
+/// incrementally towards observed targets.
+pub trait ValueFunctionApprox<S> {
+    /// Returns the approximate value of `state`.
+    fn evaluate(&self, state: &S) -> f64;
+
+    /// Moves the approximate value of `state` towards `target` with step size `alpha`.
+    fn update(&mut self, state: &S, target: f64, alpha: f64);
+}
+
+// --------------------------------------------------------------------------------------
+
+// Struct: `Tabular` ====================================================================
+
+/// An exact tabular representation of a value function, storing one value per state.
+/// States that have never been updated have value zero.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Tabular<S>
+where
+    S: Eq + Hash,
+{
+    values: HashMap<S, f64>,
+}
+
+impl<S> Tabular<S>
+where
+    S: Eq + Hash,
+{
+    /// Create a table in which every state has value zero.
+    pub fn new() -> Self {
+        Tabular {
+            values: HashMap::new(),
+        }
+    }
+
+    /// Returns the values of the states that have been updated.
+    pub fn values(&self) -> &HashMap<S, f64> {
+        &self.values
+    }
+
+    /// Consumes the table and returns the values of the states that have been updated.
+    pub fn into_values(self) -> HashMap<S, f64> {
+        self.values
+    }
+}
+
+impl<S> From<HashMap<S, f64>> for Tabular<S>
+where
+    S: Eq + Hash,
+{
+    fn from(values: HashMap<S, f64>) -> Self {
+        Tabular { values }
+    }
+}
+
+impl<S> ValueFunctionApprox<S> for Tabular<S>
+where
+    S: Eq + Hash + Clone,
+{
+    fn evaluate(&self, state: &S) -> f64 {
+        self.values.get(state).copied().unwrap_or(0.0)
+    }
+
+    /// Applies V(s) ← V(s) + α (target - V(s)).
+    fn update(&mut self, state: &S, target: f64, alpha: f64) {
+        let value = self.values.entry(state.clone()).or_insert(0.0);
+        *value += alpha * (target - *value);
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+// Struct: `LinearApprox` ===============================================================
+
+/// A linear approximation V(s) = w · φ(s) of a value function, where φ maps each state
+/// to a vector of features.
+#[derive(Clone)]
+pub struct LinearApprox<S, F>
+where
+    F: Fn(&S) -> Vec<f64>,
+{
+    weights: Vec<f64>,
+    features: F,
+    _phantom: PhantomData<fn(&S)>,
+}
+
+impl<S, F> LinearApprox<S, F>
+where
+    F: Fn(&S) -> Vec<f64>,
+{
+    /// Create a linear approximation over `num_features` features computed by `features`,
+    /// with all weights starting at zero.
+    pub fn new(num_features: usize, features: F) -> Self {
+        LinearApprox {
+            weights: vec![0.0; num_features],
+            features,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Returns the current weights.
+    pub fn weights(&self) -> &[f64] {
+        &self.weights
+    }
+
+    /// Returns the approximate value w · φ(s) of `state`.
+    ///
+    /// Panics if the feature function does not return one feature per weight.
+    pub fn evaluate(&self, state: &S) -> f64 {
+        let phi = (self.features)(state);
+        assert_eq!(phi.len(), self.weights.len(), "wrong number of features");
+        self.weights.iter().zip(&phi).map(|(w, x)| w * x).sum()
+    }
+
+    /// Takes a gradient step of size `alpha` on the squared error ½ (target - V(s))², which
+    /// is w ← w + α (target - w · φ(s)) φ(s).
+    ///
+    /// Panics if the feature function does not return one feature per weight.
+    pub fn update(&mut self, state: &S, target: f64, alpha: f64) {
+        let phi = (self.features)(state);
+        assert_eq!(phi.len(), self.weights.len(), "wrong number of features");
+        let value: f64 = self.weights.iter().zip(&phi).map(|(w, x)| w * x).sum();
+        let step = alpha * (target - value);
+        for (w, x) in self.weights.iter_mut().zip(&phi) {
+            *w += step * x;
+        }
+    }
+}
+
+impl<S, F> ValueFunctionApprox<S> for LinearApprox<S, F>
+where
+    F: Fn(&S) -> Vec<f64>,
+{
+    fn evaluate(&self, state: &S) -> f64 {
+        LinearApprox::evaluate(self, state)
+    }
+
+    fn update(&mut self, state: &S, target: f64, alpha: f64) {
+        LinearApprox::update(self, state, target, alpha)
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fitting_linear_targets_decreases_the_loss() {
+        // Targets 2x - 1 lie exactly in the span of the features (1, x).
+        let states = [0.0, 0.25, 0.5, 0.75, 1.0];
+        let target = |x: f64| 2.0 * x - 1.0;
+        let mut vf = LinearApprox::new(2, |x: &f64| vec![1.0, *x]);
+        let loss = |vf: &LinearApprox<f64, _>| -> f64 {
+            states
+                .iter()
+                .map(|x| (target(*x) - vf.evaluate(x)).powi(2))
+                .sum()
+        };
+
+        let mut losses = vec![loss(&vf)];
+        for _ in 0..200 {
+            for x in &states {
+                vf.update(x, target(*x), 0.1);
+            }
+            losses.push(loss(&vf));
+        }
+        assert!(losses.windows(2).all(|pair| pair[1] < pair[0]));
+        assert!(losses[losses.len() - 1] < 1e-6);
+        assert!((vf.weights()[0] + 1.0).abs() < 1e-3);
+        assert!((vf.weights()[1] - 2.0).abs() < 1e-3);
+    }
+}
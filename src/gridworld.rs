@@ -0,0 +1,255 @@
+use crate::distribution::Categorical;
+use crate::error::{Error, Result};
+use crate::markov_decision_process::FiniteMarkovDecisionProcess;
+use crate::markov_process::{NonTerminal, Reward, State, StateReward, Terminal};
+use std::collections::{HashMap, HashSet};
+
+/// A cell of a grid, given as `(row, column)` with `(0, 0)` in the top-left corner.
+pub type Cell = (usize, usize);
+
+/// A gridworld decision process over cells, as built by [`GridworldBuilder`].
+pub type Gridworld = FiniteMarkovDecisionProcess<Cell, Direction, StateReward<Cell>>;
+
+// Enum: `Direction` ====================================================================
+
+/// A move to a neighbouring cell of a grid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    /// All four directions.
+    pub const ALL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+    ];
+
+    /// Returns the two directions at right angles to this one.
+    fn perpendicular(self) -> [Direction; 2] {
+        match self {
+            Direction::Up | Direction::Down => [Direction::Left, Direction::Right],
+            Direction::Left | Direction::Right => [Direction::Up, Direction::Down],
+        }
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+// Struct: `GridworldBuilder` ===========================================================
+
+/// A builder for gridworld decision processes.
+///
+/// Every cell that is neither a wall nor a terminal cell is a non-terminal state in which
+/// each [`Direction`] is available. A move goes in the chosen direction, except that with
+/// the slip probability it goes in one of the two perpendicular directions instead, each
+/// equally likely. A move into a wall or off the grid leaves the agent where it is. Each
+/// move has reward `-step_cost`, plus the reward of the terminal cell it enters, if any.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GridworldBuilder {
+    rows: usize,
+    cols: usize,
+    walls: HashSet<Cell>,
+    terminals: HashMap<Cell, f64>,
+    step_cost: f64,
+    slip: f64,
+}
+
+impl GridworldBuilder {
+    /// Create a builder for an empty grid of `rows` by `cols` cells, with no step cost and
+    /// deterministic moves.
+    pub fn new(rows: usize, cols: usize) -> Self {
+        GridworldBuilder {
+            rows,
+            cols,
+            walls: HashSet::new(),
+            terminals: HashMap::new(),
+            step_cost: 0.0,
+            slip: 0.0,
+        }
+    }
+
+    /// Makes `cell` a wall, which cannot be entered.
+    ///
+    /// Panics if `cell` is outside of the grid.
+    pub fn wall(mut self, cell: Cell) -> Self {
+        self.check_cell(cell);
+        self.terminals.remove(&cell);
+        self.walls.insert(cell);
+        self
+    }
+
+    /// Makes `cell` a terminal cell whose entry earns `reward`.
+    ///
+    /// Panics if `cell` is outside of the grid.
+    pub fn terminal(mut self, cell: Cell, reward: f64) -> Self {
+        self.check_cell(cell);
+        self.walls.remove(&cell);
+        self.terminals.insert(cell, reward);
+        self
+    }
+
+    /// Sets the cost subtracted from the reward of every move.
+    pub fn step_cost(mut self, step_cost: f64) -> Self {
+        self.step_cost = step_cost;
+        self
+    }
+
+    /// Sets the probability that a move slips to a perpendicular direction.
+    pub fn slip_probability(mut self, slip: f64) -> Self {
+        self.slip = slip;
+        self
+    }
+
+    /// Returns the gridworld described by this builder.
+    ///
+    /// Returns [`Error::InvalidProbability`] unless the slip probability lies in [0, 1].
+    pub fn build(self) -> Result<Gridworld> {
+        if !(0.0..=1.0).contains(&self.slip) {
+            return Err(Error::InvalidProbability(self.slip));
+        }
+
+        let mut mapping = HashMap::new();
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                let cell = (row, col);
+                if self.walls.contains(&cell) || self.terminals.contains_key(&cell) {
+                    continue;
+                }
+                let actions = Direction::ALL
+                    .iter()
+                    .map(|&direction| Ok((direction, self.step(cell, direction)?)))
+                    .collect::<Result<HashMap<_, _>>>()?;
+                mapping.insert(cell, actions);
+            }
+        }
+        FiniteMarkovDecisionProcess::new(mapping)
+    }
+
+    /// Returns the joint distribution of the next state and the reward for trying to move
+    /// in `direction` from `cell`.
+    fn step(&self, cell: Cell, direction: Direction) -> Result<StateReward<Cell>> {
+        let [side_a, side_b] = direction.perpendicular();
+        let moves = [
+            (direction, 1.0 - self.slip),
+            (side_a, self.slip / 2.0),
+            (side_b, self.slip / 2.0),
+        ];
+
+        let mut weights = HashMap::new();
+        for (direction, p) in moves {
+            if p == 0.0 {
+                continue;
+            }
+            let next = self.neighbour(cell, direction);
+            let outcome = match self.terminals.get(&next) {
+                Some(reward) => (
+                    State::Terminal(Terminal::new(next)),
+                    Reward(reward - self.step_cost),
+                ),
+                None => (
+                    State::NonTerminal(NonTerminal::new(next)),
+                    Reward(-self.step_cost),
+                ),
+            };
+            *weights.entry(outcome).or_insert(0.0) += p;
+        }
+        Categorical::new(weights)
+    }
+
+    /// Returns the cell reached by moving from `cell` in `direction`, which is `cell`
+    /// itself if the move would leave the grid or enter a wall.
+    fn neighbour(&self, (row, col): Cell, direction: Direction) -> Cell {
+        let next = match direction {
+            Direction::Up => (row.wrapping_sub(1), col),
+            Direction::Down => (row + 1, col),
+            Direction::Left => (row, col.wrapping_sub(1)),
+            Direction::Right => (row, col + 1),
+        };
+        if next.0 < self.rows && next.1 < self.cols && !self.walls.contains(&next) {
+            next
+        } else {
+            (row, col)
+        }
+    }
+
+    fn check_cell(&self, (row, col): Cell) {
+        assert!(
+            row < self.rows && col < self.cols,
+            "cell ({}, {}) is outside of the {} x {} grid",
+            row,
+            col,
+            self.rows,
+            self.cols
+        );
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distribution::FiniteDistribution;
+    use crate::markov_decision_process::MarkovDecisionProcess;
+
+    /// A 3 x 3 grid with a wall right of the top-left corner and the goal, worth 10, in
+    /// the bottom-right corner.
+    fn grid(slip: f64) -> Gridworld {
+        GridworldBuilder::new(3, 3)
+            .wall((0, 1))
+            .terminal((2, 2), 10.0)
+            .step_cost(1.0)
+            .slip_probability(slip)
+            .build()
+            .unwrap()
+    }
+
+    fn moved(mdp: &Gridworld, from: Cell, direction: Direction, to: Cell) -> f64 {
+        let step = mdp.step(&NonTerminal::new(from), &direction).unwrap();
+        step.probability(&(State::NonTerminal(NonTerminal::new(to)), Reward(-1.0)))
+    }
+
+    #[test]
+    fn walls_and_terminals_are_not_states() {
+        let mdp = grid(0.0);
+        assert_eq!(mdp.mapping().len(), 7);
+        assert!(mdp.actions(&NonTerminal::new((0, 1))).is_empty());
+        assert!(mdp.actions(&NonTerminal::new((2, 2))).is_empty());
+        assert_eq!(mdp.actions(&NonTerminal::new((0, 0))).len(), 4);
+    }
+
+    #[test]
+    fn corner_cell_bumps_into_walls_and_edges() {
+        let mdp = grid(0.0);
+        for direction in [Direction::Up, Direction::Left, Direction::Right] {
+            assert_eq!(moved(&mdp, (0, 0), direction, (0, 0)), 1.0);
+        }
+        assert_eq!(moved(&mdp, (0, 0), Direction::Down, (1, 0)), 1.0);
+    }
+
+    #[test]
+    fn slipping_moves_sideways() {
+        let mdp = grid(0.2);
+        // Up and Left bump into the edges, and Right into the wall.
+        assert!((moved(&mdp, (0, 0), Direction::Up, (0, 0)) - 1.0).abs() < 1e-12);
+        assert!((moved(&mdp, (0, 0), Direction::Left, (0, 0)) - 0.9).abs() < 1e-12);
+        assert!((moved(&mdp, (0, 0), Direction::Left, (1, 0)) - 0.1).abs() < 1e-12);
+        assert!((moved(&mdp, (0, 0), Direction::Down, (1, 0)) - 0.8).abs() < 1e-12);
+    }
+
+    #[test]
+    fn entering_a_terminal_cell_pays_its_reward() {
+        let mdp = grid(0.0);
+        let step = mdp
+            .step(&NonTerminal::new((2, 1)), &Direction::Right)
+            .unwrap();
+        let goal = (State::Terminal(Terminal::new((2, 2))), Reward(9.0));
+        assert_eq!(step.probability(&goal), 1.0);
+    }
+}
@@ -1 +1,15 @@
-mod distribution;
+pub mod convergence;
+pub mod diagnostics;
+pub mod distribution;
+pub mod dynamic_programming;
+pub mod error;
+pub mod function_approx;
+pub mod gridworld;
+mod linalg;
+pub mod markov_decision_process;
+pub mod markov_process;
+pub mod monte_carlo;
+pub mod policy;
+pub mod returns;
+pub mod statistics;
+pub mod temporal_difference;
@@ -0,0 +1,50 @@
+use crate::error::{Error, Result};
+
+// [ Dense Linear Algebra ] =============================================================
+
+/// Pivots smaller than this in absolute value are treated as zero.
+const SINGULAR_TOLERANCE: f64 = 1e-12;
+
+/// Solves the linear system `A x = b` by Gaussian elimination with partial pivoting, where
+/// `a` is a dense, row-major `n x n` matrix and `n = b.len()`.
+///
+/// Returns [`Error::SingularMatrix`] if `A` is singular.
+pub(crate) fn solve(mut a: Vec<f64>, mut b: Vec<f64>) -> Result<Vec<f64>> {
+    let n = b.len();
+    debug_assert_eq!(a.len(), n * n);
+
+    for col in 0..n {
+        // Swap the row with the largest entry in this column into the pivot position.
+        let pivot = (col..n)
+            .max_by(|&i, &j| a[i * n + col].abs().total_cmp(&a[j * n + col].abs()))
+            .expect("column has at least one row");
+        if a[pivot * n + col].abs() < SINGULAR_TOLERANCE {
+            return Err(Error::SingularMatrix);
+        }
+        if pivot != col {
+            for k in 0..n {
+                a.swap(pivot * n + k, col * n + k);
+            }
+            b.swap(pivot, col);
+        }
+
+        for row in col + 1..n {
+            let factor = a[row * n + col] / a[col * n + col];
+            if factor != 0.0 {
+                for k in col..n {
+                    a[row * n + k] -= factor * a[col * n + k];
+                }
+                b[row] -= factor * b[col];
+            }
+        }
+    }
+
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let tail: f64 = (row + 1..n).map(|k| a[row * n + k] * x[k]).sum();
+        x[row] = (b[row] - tail) / a[row * n + row];
+    }
+    Ok(x)
+}
+
+// --------------------------------------------------------------------------------------
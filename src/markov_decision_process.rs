@@ -0,0 +1,507 @@
+use crate::distribution::{Categorical, Distribution, FiniteDistribution};
+use crate::error::{Error, Result};
+use crate::markov_process::{
+    FiniteMarkovRewardProcess, NonTerminal, Reward, State, StateIndex, StateReward,
+};
+use crate::policy::Policy;
+use rand::rngs::ThreadRng;
+use rand::Rng;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+/// An action-value function mapping each pair of a non-terminal state and an action to
+/// its value.
+pub type ActionValueFunction<S, A> = HashMap<(NonTerminal<S>, A), f64>;
+
+// Trait: `MarkovDecisionProcess` =======================================================
+
+/// A process in which an agent picks an action in each non-terminal state, and the next
+/// state and reward depend only on the current state and the chosen action.
+pub trait MarkovDecisionProcess<S, A> {
+    /// The joint distribution of successor states and rewards returned by
+    /// [`MarkovDecisionProcess::step`].
+    type Step: Distribution<(State<S>, Reward)>;
+
+    /// Returns the actions available in `state`.
+    fn actions(&self, state: &NonTerminal<S>) -> Vec<A>;
+
+    /// Returns the joint distribution of the next state and the reward for taking `action`
+    /// in `state`, or `None` if `action` is not available in `state`.
+    fn step(&self, state: &NonTerminal<S>, action: &A) -> Option<&Self::Step>;
+}
+
+// --------------------------------------------------------------------------------------
+
+// Struct: `FiniteMarkovDecisionProcess` ================================================
+
+/// A markov decision process with finite state and action spaces.
+pub struct FiniteMarkovDecisionProcess<S, A, X>
+where
+    S: Ord + Hash,
+    A: Ord + Hash,
+    X: FiniteDistribution<(State<S>, Reward)>,
+{
+    non_terminal_states: Vec<NonTerminal<S>>,
+    mapping: HashMap<NonTerminal<S>, HashMap<A, X>>,
+}
+
+impl<S, A, X> FiniteMarkovDecisionProcess<S, A, X>
+where
+    S: Ord + Hash,
+    A: Ord + Hash,
+    X: FiniteDistribution<(State<S>, Reward)>,
+{
+    /// Create a finite markov decision process from a map of each non-terminal state to
+    /// the joint distribution of successor states and rewards for each of its actions.
+    ///
+    /// A successor state is non-terminal exactly when it is a key of `mapping`. Returns
+    /// [`Error::NoActions`] if some state has no actions, and
+    /// [`Error::InconsistentTerminal`] if a successor marked terminal is a key or a
+    /// successor marked non-terminal is not.
+    pub fn new(mapping: HashMap<S, HashMap<A, X>>) -> Result<Self>
+    where
+        S: Ord + Clone,
+    {
+        if mapping.values().any(|actions| actions.is_empty()) {
+            return Err(Error::NoActions);
+        }
+        let inconsistent = mapping.values().flat_map(HashMap::values).any(|step| {
+            step.table().keys().any(|(next_state, _)| match next_state {
+                State::Terminal(state) => mapping.contains_key(state.state()),
+                State::NonTerminal(state) => !mapping.contains_key(state.state()),
+            })
+        });
+        if inconsistent {
+            return Err(Error::InconsistentTerminal);
+        }
+
+        let mapping: HashMap<NonTerminal<S>, HashMap<A, X>> = mapping
+            .into_iter()
+            .map(|(state, actions)| (NonTerminal::new(state), actions))
+            .collect();
+        let mut non_terminal_states: Vec<NonTerminal<S>> = mapping.keys().cloned().collect();
+        non_terminal_states.sort();
+
+        Ok(FiniteMarkovDecisionProcess {
+            non_terminal_states,
+            mapping,
+        })
+    }
+
+    /// Returns the non-terminal states in ascending order, which does not depend on the
+    /// iteration order of the map this process was created from.
+    pub fn non_terminal_states(&self) -> &[NonTerminal<S>] {
+        &self.non_terminal_states
+    }
+
+    /// Returns the number of non-terminal states of this process.
+    pub fn num_states(&self) -> usize {
+        self.non_terminal_states.len()
+    }
+
+    /// Returns the numbering of the non-terminal states in the order of
+    /// [`FiniteMarkovDecisionProcess::non_terminal_states`].
+    pub fn state_index(&self) -> StateIndex<'_, S> {
+        StateIndex::new(&self.non_terminal_states)
+    }
+
+    /// Returns the map of each non-terminal state to the joint distribution of successor
+    /// states and rewards for each of its actions.
+    pub fn mapping(&self) -> &HashMap<NonTerminal<S>, HashMap<A, X>> {
+        &self.mapping
+    }
+
+    /// Returns the markov reward process obtained by following `policy` in this process.
+    /// The transition out of each state is the average of its actions' transitions,
+    /// weighted by the probability of `policy` choosing each action.
+    ///
+    /// Returns [`Error::InvalidPolicy`] if `policy` does not cover some state or picks an
+    /// action that is not available in it.
+    pub fn apply_policy<P>(
+        &self,
+        policy: &P,
+    ) -> Result<FiniteMarkovRewardProcess<S, StateReward<S>>>
+    where
+        S: Clone,
+        P: Policy<S, A>,
+        P::ActionDist: FiniteDistribution<A>,
+    {
+        let mut transition_reward_map = HashMap::with_capacity(self.mapping.len());
+        for (state, actions) in &self.mapping {
+            let action_dist = policy.act(state).ok_or(Error::InvalidPolicy)?;
+
+            let mut weights: HashMap<(State<S>, Reward), f64> = HashMap::new();
+            for (action, &p_action) in action_dist.table() {
+                let step = actions.get(action).ok_or(Error::InvalidPolicy)?;
+                for (outcome, &p) in step.table() {
+                    *weights.entry(outcome.clone()).or_insert(0.0) += p_action * p;
+                }
+            }
+            transition_reward_map.insert(state.state().clone(), Categorical::new(weights)?);
+        }
+
+        FiniteMarkovRewardProcess::new(transition_reward_map)
+    }
+
+    /// Returns an iterator over the `(state, action, reward, next_state)` transitions of a
+    /// single episode: a start state sampled from `start_state_dist`, then actions sampled
+    /// from `policy` and transitions sampled from this process, until a terminal state is
+    /// reached. The episode also ends at a state that this process has no actions for; in
+    /// particular such a start state gives an empty episode.
+    ///
+    /// The iterator panics if `policy` does not cover a state it visits or picks an action
+    /// that is not available in it.
+    pub fn simulate<'a, D, P>(
+        &'a self,
+        start_state_dist: &D,
+        policy: &'a P,
+    ) -> SimulateActionsIter<'a, S, A, X, P>
+    where
+        D: Distribution<S>,
+        P: Policy<S, A>,
+    {
+        self.simulate_with(start_state_dist, policy, rand::thread_rng())
+    }
+
+    /// Like [`FiniteMarkovDecisionProcess::simulate`], drawing the start state, the actions
+    /// and the transitions with [`Distribution::sample_with`] using the random number
+    /// generator `rng`, so that the episode is reproducible when `rng` is seeded.
+    pub fn simulate_with<'a, D, P, R>(
+        &'a self,
+        start_state_dist: &D,
+        policy: &'a P,
+        mut rng: R,
+    ) -> SimulateActionsIter<'a, S, A, X, P, R>
+    where
+        D: Distribution<S>,
+        P: Policy<S, A>,
+        R: Rng,
+    {
+        let start = NonTerminal::new(start_state_dist.sample_with(&mut rng));
+        SimulateActionsIter {
+            process: self,
+            policy,
+            state: self.mapping.contains_key(&start).then_some(start),
+            rng,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<S, A, X> MarkovDecisionProcess<S, A> for FiniteMarkovDecisionProcess<S, A, X>
+where
+    S: Ord + Hash,
+    A: Ord + Hash + Clone,
+    X: FiniteDistribution<(State<S>, Reward)>,
+{
+    type Step = X;
+
+    /// Returns the actions available in `state` in ascending order, so that methods
+    /// breaking ties in favor of the first action pick the least one in every run.
+    fn actions(&self, state: &NonTerminal<S>) -> Vec<A> {
+        let mut actions: Vec<A> = self
+            .mapping
+            .get(state)
+            .map(|actions| actions.keys().cloned().collect())
+            .unwrap_or_default();
+        actions.sort();
+        actions
+    }
+
+    fn step(&self, state: &NonTerminal<S>, action: &A) -> Option<&X> {
+        self.mapping.get(state)?.get(action)
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+// Struct: `SimulateActionsIter` ========================================================
+
+/// An iterator over the transitions of a single simulated episode of a finite markov
+/// decision process following a policy, drawn using the random number generator `R` that
+/// it owns.
+///
+/// This struct is created by the [`FiniteMarkovDecisionProcess::simulate`] and
+/// [`FiniteMarkovDecisionProcess::simulate_with`] methods.
+pub struct SimulateActionsIter<'a, S, A, X, P, R = ThreadRng>
+where
+    S: Ord + Hash,
+    A: Ord + Hash,
+    X: FiniteDistribution<(State<S>, Reward)>,
+{
+    process: &'a FiniteMarkovDecisionProcess<S, A, X>,
+    policy: &'a P,
+    state: Option<NonTerminal<S>>,
+    rng: R,
+    _phantom: PhantomData<A>,
+}
+
+impl<S, A, X, P, R> Iterator for SimulateActionsIter<'_, S, A, X, P, R>
+where
+    S: Ord + Hash + Clone,
+    A: Ord + Hash,
+    X: FiniteDistribution<(State<S>, Reward)>,
+    P: Policy<S, A>,
+    R: Rng,
+{
+    type Item = (NonTerminal<S>, A, f64, State<S>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let state = self.state.take()?;
+        let action = self
+            .policy
+            .act(&state)
+            .expect("policy covers every visited state")
+            .sample_with(&mut self.rng);
+        let (next_state, reward) = self.process.mapping[&state]
+            .get(&action)
+            .expect("policy picks an available action")
+            .sample_with(&mut self.rng);
+        if let State::NonTerminal(next_state) = &next_state {
+            if self.process.mapping.contains_key(next_state) {
+                self.state = Some(next_state.clone());
+            }
+        }
+        Some((state, action, reward.0, next_state))
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distribution::{Categorical, Constant};
+    use crate::markov_process::{MarkovRewardProcess, Terminal};
+    use crate::policy::{DeterministicPolicy, StochasticPolicy};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    type Step = Categorical<(State<u32>, Reward)>;
+
+    fn step(outcomes: &[(State<u32>, f64, f64)]) -> Step {
+        Categorical::new(
+            outcomes
+                .iter()
+                .map(|&(state, reward, p)| ((state, Reward(reward)), p))
+                .collect(),
+        )
+        .unwrap()
+    }
+
+    fn non_terminal(state: u32) -> State<u32> {
+        State::NonTerminal(NonTerminal::new(state))
+    }
+
+    fn terminal(state: u32) -> State<u32> {
+        State::Terminal(Terminal::new(state))
+    }
+
+    /// A corridor of cells 0 and 1 with the goal 2 to the right of cell 1. Moving left
+    /// from cell 0 bumps into the wall and stays put.
+    fn corridor() -> FiniteMarkovDecisionProcess<u32, char, Step> {
+        FiniteMarkovDecisionProcess::new(HashMap::from([
+            (
+                0,
+                HashMap::from([
+                    ('L', step(&[(non_terminal(0), -1.0, 1.0)])),
+                    ('R', step(&[(non_terminal(1), -1.0, 1.0)])),
+                ]),
+            ),
+            (
+                1,
+                HashMap::from([
+                    ('L', step(&[(non_terminal(0), -1.0, 1.0)])),
+                    ('R', step(&[(terminal(2), 10.0, 1.0)])),
+                ]),
+            ),
+        ]))
+        .unwrap()
+    }
+
+    fn uniform_policy() -> StochasticPolicy<u32, char> {
+        let uniform = Categorical::new(HashMap::from([('L', 1.0), ('R', 1.0)])).unwrap();
+        StochasticPolicy::new(HashMap::from([(0, uniform.clone()), (1, uniform)]))
+    }
+
+    #[test]
+    fn wires_actions_and_transitions() {
+        let mdp = corridor();
+        assert_eq!(mdp.mapping().len(), 2);
+
+        let mut actions = mdp.actions(&NonTerminal::new(0));
+        actions.sort();
+        assert_eq!(actions, vec!['L', 'R']);
+        assert!(mdp.actions(&NonTerminal::new(2)).is_empty());
+
+        let bump = mdp.step(&NonTerminal::new(0), &'L').unwrap();
+        assert_eq!(bump.probability(&(non_terminal(0), Reward(-1.0))), 1.0);
+        let goal = mdp.step(&NonTerminal::new(1), &'R').unwrap();
+        assert_eq!(goal.probability(&(terminal(2), Reward(10.0))), 1.0);
+        assert!(mdp.step(&NonTerminal::new(1), &'U').is_none());
+    }
+
+    #[test]
+    fn simulated_actions_are_available() {
+        let mdp = corridor();
+        let policy = uniform_policy();
+        let start = Categorical::new(HashMap::from([(0, 1.0), (1, 1.0)])).unwrap();
+        for seed in 0..20 {
+            let episode: Vec<_> = mdp
+                .simulate_with(&start, &policy, StdRng::seed_from_u64(seed))
+                .take(1_000)
+                .collect();
+            for (state, action, reward, next_state) in &episode {
+                let step = mdp.step(state, action).expect("action is available");
+                assert!(step.probability(&(*next_state, Reward(*reward))) > 0.0);
+            }
+            let (.., last) = episode.last().unwrap();
+            assert_eq!(*last, terminal(2));
+        }
+    }
+
+    #[test]
+    fn seeded_episodes_are_reproducible() {
+        let mdp = corridor();
+        let policy = uniform_policy();
+        let episode = |seed| -> Vec<_> {
+            mdp.simulate_with(&Constant::new(0), &policy, StdRng::seed_from_u64(seed))
+                .collect()
+        };
+        assert_eq!(episode(8), episode(8));
+    }
+
+    #[test]
+    fn rejects_state_without_actions() {
+        let mapping: HashMap<u32, HashMap<char, Step>> = HashMap::from([(0, HashMap::new())]);
+        let result = FiniteMarkovDecisionProcess::new(mapping);
+        assert!(matches!(result, Err(Error::NoActions)));
+    }
+
+    #[test]
+    fn rejects_inconsistently_tagged_successors() {
+        let terminal_key = HashMap::from([
+            (0, HashMap::from([('R', step(&[(terminal(1), 0.0, 1.0)]))])),
+            (1, HashMap::from([('R', step(&[(terminal(2), 0.0, 1.0)]))])),
+        ]);
+        let dangling = HashMap::from([(
+            0,
+            HashMap::from([('R', step(&[(non_terminal(1), 0.0, 1.0)]))]),
+        )]);
+
+        for mapping in [terminal_key, dangling] {
+            let result = FiniteMarkovDecisionProcess::new(mapping);
+            assert!(matches!(result, Err(Error::InconsistentTerminal)));
+        }
+    }
+
+    #[test]
+    fn single_action_process_gives_the_obvious_reward_process() {
+        let transitions = HashMap::from([
+            (0, step(&[(non_terminal(1), 2.0, 1.0)])),
+            (
+                1,
+                step(&[(non_terminal(0), 1.0, 0.5), (terminal(2), 3.0, 0.5)]),
+            ),
+        ]);
+        let mapping = transitions
+            .iter()
+            .map(|(&s, step)| (s, HashMap::from([('x', step.clone())])))
+            .collect();
+        let mdp = FiniteMarkovDecisionProcess::new(mapping).unwrap();
+        let policy = DeterministicPolicy::new(HashMap::from([(0, 'x'), (1, 'x')]));
+        let mrp = mdp.apply_policy(&policy).unwrap();
+
+        let expected = FiniteMarkovRewardProcess::new(transitions).unwrap();
+        let (_, states) = expected.markov_process().get_transition_matrix();
+        assert_eq!(
+            mrp.markov_process().get_transition_matrix().1.len(),
+            states.len()
+        );
+        for state in states {
+            let derived = mrp.transition_reward(state).unwrap().table();
+            assert_eq!(derived, expected.transition_reward(state).unwrap().table());
+        }
+    }
+
+    #[test]
+    fn applying_a_policy_averages_transitions_over_actions() {
+        let mrp = corridor().apply_policy(&uniform_policy()).unwrap();
+        let transition = mrp.transition_reward(&NonTerminal::new(0)).unwrap();
+        assert_eq!(
+            transition.probability(&(non_terminal(0), Reward(-1.0))),
+            0.5
+        );
+        assert_eq!(
+            transition.probability(&(non_terminal(1), Reward(-1.0))),
+            0.5
+        );
+
+        let partial = DeterministicPolicy::new(HashMap::from([(0, 'R')]));
+        assert!(matches!(
+            corridor().apply_policy(&partial),
+            Err(Error::InvalidPolicy)
+        ));
+    }
+
+    #[test]
+    fn actions_are_listed_in_ascending_order() {
+        let actions: HashMap<char, Step> = ('a'..='z')
+            .rev()
+            .map(|action| (action, step(&[(terminal(1), 0.0, 1.0)])))
+            .collect();
+        let fan = FiniteMarkovDecisionProcess::new(HashMap::from([(0, actions)])).unwrap();
+        let state = NonTerminal::new(0);
+        assert_eq!(fan.actions(&state), ('a'..='z').collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn potential_based_shaping_preserves_the_optimal_policy() {
+        let mdp = corridor();
+        let gamma = 0.9;
+        // A potential that favours moving left, and is zero on the terminal state.
+        let potential = |state: &State<u32>| match state {
+            State::NonTerminal(state) => 20.0 - 8.0 * *state.state() as f64,
+            State::Terminal(_) => 0.0,
+        };
+
+        let start = NonTerminal::new(0);
+        let mut values = Vec::new();
+        for (a0, a1) in [('L', 'L'), ('L', 'R'), ('R', 'L'), ('R', 'R')] {
+            let policy = DeterministicPolicy::new(HashMap::from([(0, a0), (1, a1)]));
+            let mrp = mdp.apply_policy(&policy).unwrap();
+            let vf = mrp.value_function(gamma).unwrap();
+            let shaped = mrp.shape_rewards(gamma, potential).unwrap();
+            let shaped_vf = shaped.value_function(gamma).unwrap();
+            for (state, value) in &vf {
+                let phi = potential(&State::NonTerminal(*state));
+                assert!((shaped_vf[state] - (value - phi)).abs() < 1e-9);
+            }
+            values.push(((a0, a1), vf[&start], shaped_vf[&start]));
+        }
+
+        let best = values.iter().max_by(|x, y| x.1.total_cmp(&y.1)).unwrap();
+        let best_shaped = values.iter().max_by(|x, y| x.2.total_cmp(&y.2)).unwrap();
+        assert_eq!(best.0, ('R', 'R'));
+        assert_eq!(best_shaped.0, ('R', 'R'));
+    }
+
+    #[test]
+    fn state_order_does_not_depend_on_map_order() {
+        let line = || {
+            let mapping: HashMap<u32, HashMap<char, Step>> = (0..32)
+                .map(|s| {
+                    let next = if s == 31 {
+                        terminal(32)
+                    } else {
+                        non_terminal(s + 1)
+                    };
+                    (s, HashMap::from([('r', step(&[(next, 1.0, 1.0)]))]))
+                })
+                .collect();
+            FiniteMarkovDecisionProcess::new(mapping).unwrap()
+        };
+        assert_eq!(line().non_terminal_states(), line().non_terminal_states());
+    }
+}
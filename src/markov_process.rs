@@ -1,30 +1,68 @@
-use rand::seq::IteratorRandom;
-
-use crate::distribution::{Distribution, FiniteDistribution};
-use std::collections::binary_heap::Iter;
-use std::collections::HashMap;
-use std::hash::Hash;
+use crate::convergence::{Convergence, ConvergenceReport};
+use crate::distribution::{marginalize, Categorical, Distribution, FiniteDistribution};
+use crate::error::{Error, Result};
+use crate::linalg;
+use rand::rngs::{StdRng, ThreadRng};
+use rand::{Rng, SeedableRng};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Display;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::ops::{Add, Mul};
 
 // [ States ] ===========================================================================
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct Terminal<S> {
     state: S,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+impl<S> Terminal<S> {
+    pub fn new(state: S) -> Self {
+        Terminal { state }
+    }
+
+    /// Returns a reference to the wrapped state.
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct NonTerminal<S> {
     state: S,
 }
 
-#[derive(Clone, Copy, Debug)]
+impl<S> NonTerminal<S> {
+    pub fn new(state: S) -> Self {
+        NonTerminal { state }
+    }
+
+    /// Returns a reference to the wrapped state.
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+}
+
+/// A state of a process, tagged as terminal or non-terminal.
+///
+/// States compare and hash by variant as well as by the wrapped state, so a terminal and
+/// a non-terminal state wrapping the same value are different keys of a `HashMap`.
+/// Terminal states order before non-terminal ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum State<S> {
     Terminal(Terminal<S>),
     NonTerminal(NonTerminal<S>),
 }
 
 impl<S> State<S> {
-    fn on_non_terminal<F, X>(self, f: F, default: X) -> X
+    pub fn on_non_terminal<F, X>(self, f: F, default: X) -> X
     where
         F: Fn(&NonTerminal<S>) -> X,
     {
@@ -33,27 +71,246 @@ impl<S> State<S> {
             State::NonTerminal(state) => f(&state),
         }
     }
+
+    pub fn on_terminal<F, X>(self, f: F, default: X) -> X
+    where
+        F: Fn(&Terminal<S>) -> X,
+    {
+        match self {
+            State::Terminal(state) => f(&state),
+            State::NonTerminal(_) => default,
+        }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, State::Terminal(_))
+    }
+
+    pub fn is_non_terminal(&self) -> bool {
+        matches!(self, State::NonTerminal(_))
+    }
+
+    /// Returns the wrapped state, whether it is terminal or not.
+    pub fn state(&self) -> &S {
+        match self {
+            State::Terminal(state) => state.state(),
+            State::NonTerminal(state) => state.state(),
+        }
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+// Struct: `StateIndex` =================================================================
+
+/// A numbering 0, 1, ... of non-terminal states in a fixed order, converting between
+/// states and the indices of the vectors and matrices over them.
+///
+/// [`FiniteMarkovProcess::state_index`] and the analogous methods of the other finite
+/// processes number their states in the order used by their transition matrices, which
+/// is fixed when the process is created and does not depend on the iteration order of
+/// the map it was created from.
+#[derive(Clone, Debug)]
+pub struct StateIndex<'a, S> {
+    states: &'a [NonTerminal<S>],
+    index: HashMap<&'a NonTerminal<S>, usize>,
+}
+
+impl<'a, S> StateIndex<'a, S>
+where
+    S: Eq + Hash,
+{
+    /// Create the index numbering each of `states` by its position.
+    ///
+    /// Panics if a state appears more than once.
+    pub fn new(states: &'a [NonTerminal<S>]) -> Self {
+        let index: HashMap<&NonTerminal<S>, usize> =
+            states.iter().enumerate().map(|(i, s)| (s, i)).collect();
+        assert!(index.len() == states.len(), "states must be distinct");
+        StateIndex { states, index }
+    }
+
+    /// Returns the index of `state`, or `None` if it is not numbered by this index.
+    pub fn index_of(&self, state: &NonTerminal<S>) -> Option<usize> {
+        self.index.get(state).copied()
+    }
+
+    /// Returns the state with index `i`.
+    ///
+    /// Panics if `i` is not less than [`StateIndex::len`].
+    pub fn state_at(&self, i: usize) -> &'a NonTerminal<S> {
+        &self.states[i]
+    }
+
+    /// Returns the states in the order of their indices.
+    pub fn states(&self) -> &'a [NonTerminal<S>] {
+        self.states
+    }
+
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
 }
 
 // --------------------------------------------------------------------------------------
 
 // Trait: `MarkovProcess` ===============================================================
 
+/// A process whose next state depends only on its current state.
 pub trait MarkovProcess<S> {
-    fn transition<D>(self, state: NonTerminal<S>) -> D
+    /// The distribution of successor states returned by [`MarkovProcess::transition`].
+    type Transition: Distribution<S>;
+
+    /// Returns the distribution of the next state given the current `state`, or `None`
+    /// if `state` is not a non-terminal state of this process.
+    fn transition(&self, state: &NonTerminal<S>) -> Option<&Self::Transition>;
+
+    /// Returns an iterator over a single trace of the process: a start state sampled from
+    /// `start_state_dist`, followed by successive states sampled from their transition
+    /// distributions. The trace ends after the first terminal state it yields.
+    fn simulate_iter<D>(&self, start_state_dist: D) -> SimulateIter<'_, S, Self>
     where
-        D: Distribution<S>;
+        Self: Sized,
+        D: Distribution<S>,
+    {
+        self.simulate_iter_with(start_state_dist, rand::thread_rng())
+    }
 
-    fn simulate_iter<D, Z>(self, start_state_dist: D) -> Z
+    /// Like [`MarkovProcess::simulate_iter`], drawing every state with
+    /// [`Distribution::sample_with`] using the random number generator `rng`, so that the
+    /// trace is reproducible when `rng` is seeded.
+    fn simulate_iter_with<D, R>(
+        &self,
+        start_state_dist: D,
+        mut rng: R,
+    ) -> SimulateIter<'_, S, Self, R>
     where
+        Self: Sized,
         D: Distribution<S>,
-        Z: Iterator<Item = State<S>>;
+        R: Rng,
+    {
+        let start = start_state_dist.sample_with(&mut rng);
+        SimulateIter {
+            process: self,
+            next_state: Some(to_state(self, start)),
+            rng,
+        }
+    }
 
-    fn traces_iter<D, Y, Z>(self, start_state_dist: D) -> Z
+    /// Returns an infinite iterator of independent traces of the process, each starting
+    /// from a fresh sample of `start_state_dist`.
+    fn traces_iter<D>(&self, start_state_dist: D) -> TracesIter<'_, S, Self, D>
     where
+        Self: Sized,
         D: Distribution<S>,
-        Y: Iterator<Item = State<S>>,
-        Z: Iterator<Item = Y>;
+    {
+        self.traces_iter_with(start_state_dist, rand::thread_rng())
+    }
+
+    /// Like [`MarkovProcess::traces_iter`], with each trace drawn using its own generator
+    /// seeded from the random number generator `rng`, so that the traces are reproducible
+    /// when `rng` is seeded.
+    fn traces_iter_with<D, R>(&self, start_state_dist: D, rng: R) -> TracesIter<'_, S, Self, D, R>
+    where
+        Self: Sized,
+        D: Distribution<S>,
+        R: Rng,
+    {
+        TracesIter {
+            process: self,
+            start_state_dist,
+            rng,
+            _phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+/// Wraps `state` as a non-terminal state if `process` has a transition out of it, and as a
+/// terminal state otherwise.
+fn to_state<S, M>(process: &M, state: S) -> State<S>
+where
+    M: MarkovProcess<S>,
+{
+    let state = NonTerminal::new(state);
+    match process.transition(&state) {
+        Some(_) => State::NonTerminal(state),
+        None => State::Terminal(Terminal::new(state.state)),
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+// Struct: `SimulateIter` ===============================================================
+
+/// An iterator over the states of a single simulated trace of a markov process, drawn
+/// using the random number generator `R` that it owns.
+///
+/// This struct is created by the [`MarkovProcess::simulate_iter`] and
+/// [`MarkovProcess::simulate_iter_with`] methods.
+pub struct SimulateIter<'a, S, M, R = ThreadRng> {
+    process: &'a M,
+    next_state: Option<State<S>>,
+    rng: R,
+}
+
+impl<S, M, R> Iterator for SimulateIter<'_, S, M, R>
+where
+    M: MarkovProcess<S>,
+    R: Rng,
+{
+    type Item = State<S>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let state = self.next_state.take()?;
+        if let State::NonTerminal(non_terminal) = &state {
+            let successor = self
+                .process
+                .transition(non_terminal)
+                .expect("non-terminal state has a transition")
+                .sample_with(&mut self.rng);
+            self.next_state = Some(to_state(self.process, successor));
+        }
+        Some(state)
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+// Struct: `TracesIter` =================================================================
+
+/// An iterator over independent simulated traces of a markov process. Each trace owns a
+/// generator seeded from the random number generator `R`.
+///
+/// This struct is created by the [`MarkovProcess::traces_iter`] and
+/// [`MarkovProcess::traces_iter_with`] methods.
+pub struct TracesIter<'a, S, M, D, R = ThreadRng> {
+    process: &'a M,
+    start_state_dist: D,
+    rng: R,
+    _phantom: std::marker::PhantomData<S>,
+}
+
+impl<'a, S, M, D, R> Iterator for TracesIter<'a, S, M, D, R>
+where
+    M: MarkovProcess<S>,
+    D: Distribution<S>,
+    R: Rng,
+{
+    type Item = SimulateIter<'a, S, M, StdRng>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut rng = StdRng::from_rng(&mut self.rng).expect("generator can be seeded");
+        let start = self.start_state_dist.sample_with(&mut rng);
+        Some(SimulateIter {
+            process: self.process,
+            next_state: Some(to_state(self.process, start)),
+            rng,
+        })
+    }
 }
 
 // --------------------------------------------------------------------------------------
@@ -64,7 +321,7 @@ pub trait MarkovProcess<S> {
 /// (dynamic programming) work with the process.
 pub struct FiniteMarkovProcess<S, X>
 where
-    S: Eq + Hash,
+    S: Ord + Hash,
     X: FiniteDistribution<S>,
 {
     non_terminal_states: Vec<NonTerminal<S>>,
@@ -73,54 +330,1570 @@ where
 
 impl<S, X> FiniteMarkovProcess<S, X>
 where
-    S: Eq + Hash,
+    S: Ord + Hash,
     X: FiniteDistribution<S>,
 {
-    pub fn get_transition_matrix(self) -> Vec<f64> {
-        todo!()
+    /// Create a finite markov process from a map of each non-terminal state to the
+    /// distribution of its successor states. Any successor state that is not itself a
+    /// key of `transition_map` is treated as terminal.
+    ///
+    /// The non-terminal states are kept in ascending order, which indexes the vectors and
+    /// matrices returned by this process independently of the iteration order of
+    /// `transition_map`.
+    pub fn new(transition_map: HashMap<S, X>) -> Self
+    where
+        S: Ord + Clone,
+    {
+        let transition_map: HashMap<NonTerminal<S>, X> = transition_map
+            .into_iter()
+            .map(|(state, dist)| (NonTerminal::new(state), dist))
+            .collect();
+        let mut non_terminal_states: Vec<NonTerminal<S>> = transition_map.keys().cloned().collect();
+        non_terminal_states.sort();
+
+        FiniteMarkovProcess {
+            non_terminal_states,
+            transition_map,
+        }
+    }
+
+    /// Returns the non-terminal states of this process, in the order used to index the
+    /// rows and columns of [`FiniteMarkovProcess::get_transition_matrix`].
+    pub fn non_terminal_states(&self) -> &[NonTerminal<S>] {
+        &self.non_terminal_states
+    }
+
+    /// Returns the number of non-terminal states of this process.
+    pub fn num_states(&self) -> usize {
+        self.non_terminal_states.len()
+    }
+
+    /// Returns the numbering of the non-terminal states used to index the rows and
+    /// columns of [`FiniteMarkovProcess::get_transition_matrix`].
+    pub fn state_index(&self) -> StateIndex<'_, S> {
+        StateIndex::new(&self.non_terminal_states)
+    }
+
+    /// Returns the dense, row-major `n x n` matrix of transition probabilities between
+    /// the `n` non-terminal states, together with the ordering of states used to index its
+    /// rows and columns. Probability mass moving into terminal states is omitted, so rows
+    /// may sum to less than 1. The states are in ascending order.
+    pub fn get_transition_matrix(&self) -> (Vec<f64>, Vec<&NonTerminal<S>>) {
+        let index = self.state_index();
+        let n = index.len();
+        let mut matrix = vec![0.0; n * n];
+        for (i, from) in index.states().iter().enumerate() {
+            let dist = &self.transition_map[from];
+            for (j, to) in index.states().iter().enumerate() {
+                matrix[i * n + j] = dist.probability(&to.state);
+            }
+        }
+        (matrix, index.states().iter().collect())
     }
 
-    pub fn get_stationary_distribution(self) -> X {
-        todo!()
+    /// Returns the non-terminal states that transition back to themselves with probability
+    /// 1, so that a trace entering them never ends.
+    pub fn absorbing_states(&self) -> Vec<&NonTerminal<S>> {
+        self.non_terminal_states
+            .iter()
+            .filter(|state| {
+                let p = self.transition_map[*state].probability(&state.state);
+                (p - 1.0).abs() < 1e-12
+            })
+            .collect()
+    }
+
+    /// Returns whether some terminal state can be reached from `from` by a sequence of
+    /// transitions with non-zero probability.
+    pub fn is_terminal_reachable(&self, from: &NonTerminal<S>) -> bool
+    where
+        S: Clone,
+    {
+        let mut visited = HashSet::from([from.clone()]);
+        let mut queue = VecDeque::from([from.clone()]);
+        while let Some(state) = queue.pop_front() {
+            let Some(dist) = self.transition_map.get(&state) else {
+                return true;
+            };
+            for (next_state, &p) in dist.table() {
+                let next_state = NonTerminal::new(next_state.clone());
+                if p > 0.0 && visited.insert(next_state.clone()) {
+                    queue.push_back(next_state);
+                }
+            }
+        }
+        false
+    }
+
+    /// Returns a Graphviz DOT digraph of this process with one node per state, terminal
+    /// states drawn as double circles, and one edge per transition with non-zero
+    /// probability labelled by that probability.
+    ///
+    /// Nodes and edges follow the ordering of the non-terminal states, with terminal states
+    /// after them ordered by label, so the output is deterministic for a fixed ordering.
+    pub fn to_dot(&self) -> String
+    where
+        S: Display + Clone,
+    {
+        self.to_dot_with(|_, _, p| format!("{:.3}", p))
+    }
+
+    /// Like [`FiniteMarkovProcess::to_dot`], labelling the edge for the transition from
+    /// `from` to `to` with probability `p` by `edge_label(from, to, p)`.
+    fn to_dot_with<F>(&self, edge_label: F) -> String
+    where
+        S: Display + Clone,
+        F: Fn(&NonTerminal<S>, &S, f64) -> String,
+    {
+        let quote =
+            |label: String| format!("\"{}\"", label.replace('\\', "\\\\").replace('"', "\\\""));
+        let index = self.state_index();
+        let n = index.len();
+        let order = |state: &S| {
+            let position = index.index_of(&NonTerminal::new(state.clone()));
+            (position.unwrap_or(n), state.to_string())
+        };
+
+        let mut nodes: Vec<String> = self
+            .non_terminal_states
+            .iter()
+            .map(|state| format!("    {} [shape=circle];", quote(state.state.to_string())))
+            .collect();
+        let mut edges = Vec::new();
+        let mut terminals = Vec::new();
+        for from in &self.non_terminal_states {
+            let mut successors: Vec<(&S, f64)> = self.transition_map[from]
+                .table()
+                .iter()
+                .filter(|(_, &p)| p > 0.0)
+                .map(|(to, &p)| (to, p))
+                .collect();
+            successors.sort_by_cached_key(|(to, _)| order(to));
+            for (to, p) in successors {
+                if !self
+                    .transition_map
+                    .contains_key(&NonTerminal::new(to.clone()))
+                {
+                    terminals.push(to.to_string());
+                }
+                edges.push(format!(
+                    "    {} -> {} [label={}];",
+                    quote(from.state.to_string()),
+                    quote(to.to_string()),
+                    quote(edge_label(from, to, p))
+                ));
+            }
+        }
+        terminals.sort();
+        terminals.dedup();
+        nodes.extend(
+            terminals
+                .into_iter()
+                .map(|state| format!("    {} [shape=doublecircle];", quote(state))),
+        );
+
+        let mut dot = String::from("digraph {\n");
+        for line in nodes.into_iter().chain(edges) {
+            dot.push_str(&line);
+            dot.push('\n');
+        }
+        dot.push('}');
+        dot.push('\n');
+        dot
+    }
+
+    /// Returns the stationary distribution π satisfying πP = π over the non-terminal
+    /// states, found by power iteration.
+    ///
+    /// The iteration steps the lazy chain (I + P) / 2, which has the same stationary
+    /// distribution as P but is aperiodic, so periodic chains converge too. Returns `None`
+    /// if no unique stationary distribution exists: when the chain is reducible, or when
+    /// probability mass leaks into terminal states.
+    pub fn get_stationary_distribution(&self) -> Option<Categorical<S>>
+    where
+        S: Clone,
+    {
+        const CONVERGENCE: Convergence = Convergence {
+            tolerance: 1e-12,
+            max_iters: 100_000,
+        };
+
+        let (matrix, states) = self.get_transition_matrix();
+        let n = states.len();
+        if n == 0 {
+            return None;
+        }
+        let row_sums_to_one = |row: &[f64]| (row.iter().sum::<f64>() - 1.0).abs() < 1e-9;
+        if !matrix.chunks(n).all(row_sums_to_one) || !is_strongly_connected(&matrix, n) {
+            return None;
+        }
+
+        let mut pi = vec![1.0 / n as f64; n];
+        for _ in 0..CONVERGENCE.max_iters {
+            let mut next = vec![0.0; n];
+            for (row, &p_i) in matrix.chunks(n).zip(&pi) {
+                for (next_j, &p_ij) in next.iter_mut().zip(row) {
+                    *next_j += p_i * p_ij;
+                }
+            }
+            let mut delta: f64 = 0.0;
+            for (next_j, &p_j) in next.iter_mut().zip(&pi) {
+                *next_j = 0.5 * (*next_j + p_j);
+                delta = delta.max((*next_j - p_j).abs());
+            }
+            pi = next;
+
+            if CONVERGENCE.accepts(delta) {
+                let weights = states.into_iter().map(|s| s.state.clone()).zip(pi);
+                return Categorical::new(weights.collect()).ok();
+            }
+        }
+        None
+    }
+
+    /// Returns an estimate of the stationary distribution from the visit frequencies of
+    /// the non-terminal states along one simulated trace, which starts from a sample of
+    /// `start_state_dist` and runs for `num_steps` states after the first `burn_in`
+    /// states are discarded.
+    ///
+    /// Unlike [`FiniteMarkovProcess::get_stationary_distribution`] this needs no linear
+    /// algebra, but the estimate is only close once `burn_in` and `num_steps` are large
+    /// compared to the mixing time of the chain, which can be very long for chains that
+    /// are nearly reducible. The trace stops early if it reaches a terminal state. Returns
+    /// `None` if no non-terminal state is visited after the burn-in.
+    pub fn estimate_stationary_distribution<D>(
+        &self,
+        start_state_dist: D,
+        num_steps: usize,
+        burn_in: usize,
+    ) -> Option<Categorical<S>>
+    where
+        S: Clone,
+        D: Distribution<S>,
+    {
+        self.estimate_stationary_distribution_with(
+            start_state_dist,
+            num_steps,
+            burn_in,
+            rand::thread_rng(),
+        )
+    }
+
+    /// Like [`FiniteMarkovProcess::estimate_stationary_distribution`], simulating the
+    /// trace using the given random number generator.
+    pub fn estimate_stationary_distribution_with<D, R>(
+        &self,
+        start_state_dist: D,
+        num_steps: usize,
+        burn_in: usize,
+        rng: R,
+    ) -> Option<Categorical<S>>
+    where
+        S: Clone,
+        D: Distribution<S>,
+        R: Rng,
+    {
+        let mut counts: HashMap<S, f64> = HashMap::new();
+        for state in self
+            .simulate_iter_with(start_state_dist, rng)
+            .skip(burn_in)
+            .take(num_steps)
+        {
+            if let State::NonTerminal(state) = state {
+                *counts.entry(state.state).or_insert(0.0) += 1.0;
+            }
+        }
+        Categorical::new(counts).ok()
+    }
+}
+
+/// Serialized as a sequence of `(state, transition)` pairs, since states need not be valid
+/// map keys in formats such as JSON.
+#[cfg(feature = "serde")]
+impl<S, X> serde::Serialize for FiniteMarkovProcess<S, X>
+where
+    S: Ord + Hash + serde::Serialize,
+    X: FiniteDistribution<S> + serde::Serialize,
+{
+    fn serialize<Ser>(&self, serializer: Ser) -> std::result::Result<Ser::Ok, Ser::Error>
+    where
+        Ser: serde::Serializer,
+    {
+        serializer.collect_seq(
+            self.non_terminal_states
+                .iter()
+                .map(|state| (&state.state, &self.transition_map[state])),
+        )
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, S, X> serde::Deserialize<'de> for FiniteMarkovProcess<S, X>
+where
+    S: Ord + Hash + Clone + serde::Deserialize<'de>,
+    X: FiniteDistribution<S> + serde::Deserialize<'de>,
+{
+    fn deserialize<De>(deserializer: De) -> std::result::Result<Self, De::Error>
+    where
+        De: serde::Deserializer<'de>,
+    {
+        let pairs: Vec<(S, X)> = serde::Deserialize::deserialize(deserializer)?;
+        Ok(FiniteMarkovProcess::new(pairs.into_iter().collect()))
     }
 }
 
 impl<S, X> MarkovProcess<S> for FiniteMarkovProcess<S, X>
 where
-    S: Eq + Hash,
+    S: Ord + Hash,
     X: FiniteDistribution<S>,
 {
-    fn transition<D>(self, state: NonTerminal<S>) -> D
+    type Transition = X;
+
+    fn transition(&self, state: &NonTerminal<S>) -> Option<&X> {
+        self.transition_map.get(state)
+    }
+}
+// --------------------------------------------------------------------------------------
+
+/// Returns whether every state can reach every other state in the graph with an edge
+/// `i -> j` for each non-zero entry of the row-major `n x n` matrix.
+fn is_strongly_connected(matrix: &[f64], n: usize) -> bool {
+    let reaches_all = |edge: &dyn Fn(usize, usize) -> bool| {
+        let mut visited = vec![false; n];
+        let mut stack = vec![0];
+        visited[0] = true;
+        while let Some(i) = stack.pop() {
+            for (j, seen) in visited.iter_mut().enumerate() {
+                if !*seen && edge(i, j) {
+                    *seen = true;
+                    stack.push(j);
+                }
+            }
+        }
+        visited.into_iter().all(|v| v)
+    };
+    reaches_all(&|i, j| matrix[i * n + j] > 0.0) && reaches_all(&|i, j| matrix[j * n + i] > 0.0)
+}
+
+// --------------------------------------------------------------------------------------
+
+// [ Rewards ] ==========================================================================
+
+/// A real-valued reward. Unlike a bare `f64` it implements `Eq`, `Ord` and `Hash` (by
+/// comparing bit patterns, and ordering by [`f64::total_cmp`]), so `(State<S>, Reward)`
+/// pairs can be the outcomes of a [`FiniteDistribution`].
+#[derive(Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Reward(pub f64);
+
+impl Reward {
+    /// Bit pattern used for equality and hashing, with `-0.0` identified with `0.0`.
+    fn key(&self) -> u64 {
+        if self.0 == 0.0 {
+            0.0f64.to_bits()
+        } else {
+            self.0.to_bits()
+        }
+    }
+}
+
+impl PartialEq for Reward {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Reward {}
+
+impl PartialOrd for Reward {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Reward {
+    fn cmp(&self, other: &Self) -> Ordering {
+        f64::from_bits(self.key()).total_cmp(&f64::from_bits(other.key()))
+    }
+}
+
+impl Hash for Reward {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+impl From<f64> for Reward {
+    fn from(reward: f64) -> Self {
+        Reward(reward)
+    }
+}
+
+impl From<Reward> for f64 {
+    fn from(reward: Reward) -> Self {
+        reward.0
+    }
+}
+
+impl Add for Reward {
+    type Output = Reward;
+
+    fn add(self, other: Reward) -> Reward {
+        Reward(self.0 + other.0)
+    }
+}
+
+impl Mul<f64> for Reward {
+    type Output = Reward;
+
+    fn mul(self, factor: f64) -> Reward {
+        Reward(self.0 * factor)
+    }
+}
+
+/// A type of reward that can be summed and scaled, so that expected and discounted
+/// rewards can be computed. [`Reward`] is the common real-valued case; other types allow
+/// for example vector-valued rewards in multi-objective problems.
+pub trait RewardValue: Clone + Add<Output = Self> + Mul<f64, Output = Self> {
+    /// Returns the additive identity.
+    fn zero() -> Self;
+}
+
+impl RewardValue for Reward {
+    fn zero() -> Self {
+        Reward(0.0)
+    }
+}
+
+impl RewardValue for f64 {
+    fn zero() -> Self {
+        0.0
+    }
+}
+
+/// A finite joint distribution of successor states and rewards.
+pub type StateReward<S> = Categorical<(State<S>, Reward)>;
+
+/// A value function mapping each non-terminal state to its value.
+pub type ValueFunction<S> = HashMap<NonTerminal<S>, f64>;
+
+/// Returns [`Error::InvalidParameter`] unless the discount factor `gamma` lies in [0, 1),
+/// or in [0, 1] if `finite_horizon` is set, since only a bounded horizon keeps the
+/// undiscounted return finite.
+pub(crate) fn check_discount(gamma: f64, finite_horizon: bool) -> Result<()> {
+    let valid = if finite_horizon {
+        (0.0..=1.0).contains(&gamma)
+    } else {
+        (0.0..1.0).contains(&gamma)
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::InvalidParameter {
+            name: "gamma",
+            value: gamma,
+        })
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+// Trait: `MarkovRewardProcess` =========================================================
+
+/// A markov process that also yields a reward of type `R` on each transition.
+pub trait MarkovRewardProcess<S, R = Reward>: MarkovProcess<S> {
+    /// The joint distribution of successor states and rewards returned by
+    /// [`MarkovRewardProcess::transition_reward`].
+    type TransitionReward: Distribution<(State<S>, R)>;
+
+    /// Returns the joint distribution of the next state and the reward for the transition
+    /// out of `state`, or `None` if `state` is not a non-terminal state of this process.
+    fn transition_reward(&self, state: &NonTerminal<S>) -> Option<&Self::TransitionReward>;
+
+    /// Returns the expected immediate reward of the transition out of `state`, or `None`
+    /// if `state` is not a non-terminal state of this process.
+    ///
+    /// By default this is a Monte Carlo estimate from [`EXPECTED_REWARD_SAMPLES`] samples
+    /// of [`MarkovRewardProcess::transition_reward`]; finite processes compute it exactly.
+    fn expected_reward(&self, state: &NonTerminal<S>) -> Option<f64>
     where
-        D: Distribution<S>,
+        R: Clone + Into<f64>,
     {
-        todo!()
+        let transition = self.transition_reward(state)?;
+        Some(transition.expectation(|(_, reward)| reward.clone().into(), EXPECTED_REWARD_SAMPLES))
+    }
+}
+
+/// Number of samples used by the default [`MarkovRewardProcess::expected_reward`].
+pub const EXPECTED_REWARD_SAMPLES: usize = 10_000;
+
+// --------------------------------------------------------------------------------------
+
+// Struct: `FiniteMarkovRewardProcess` ==================================================
+
+/// A markov reward process with a finite state space.
+///
+/// The underlying [`FiniteMarkovProcess`] is derived by marginalizing out the rewards.
+/// Rewards are of type `R`, which defaults to [`Reward`]; the value function methods are
+/// available for that case.
+pub struct FiniteMarkovRewardProcess<S, X, R = Reward>
+where
+    S: Ord + Hash + Clone,
+    R: Eq + Hash,
+    X: FiniteDistribution<(State<S>, R)>,
+{
+    process: FiniteMarkovProcess<S, Categorical<S>>,
+    transition_reward_map: HashMap<NonTerminal<S>, X>,
+    _phantom: PhantomData<fn() -> R>,
+}
+
+impl<S, X, R> FiniteMarkovRewardProcess<S, X, R>
+where
+    S: Ord + Hash + Clone,
+    R: Eq + Hash,
+    X: FiniteDistribution<(State<S>, R)>,
+{
+    /// Create a finite markov reward process from a map of each non-terminal state to the
+    /// joint distribution of its successor states and rewards.
+    ///
+    /// As for [`FiniteMarkovProcess::new`], a successor state is non-terminal exactly when
+    /// it is a key of `transition_reward_map`. Returns [`Error::InconsistentTerminal`] if a
+    /// successor marked terminal is a key, or a successor marked non-terminal is not, so
+    /// that its tag never disagrees with the map, and [`Error::InvalidWeights`] if any
+    /// distribution has no probability mass.
+    pub fn new(transition_reward_map: HashMap<S, X>) -> Result<Self> {
+        let inconsistent = transition_reward_map.values().any(|dist| {
+            dist.table().keys().any(|(next_state, _)| match next_state {
+                State::Terminal(Terminal { state }) => transition_reward_map.contains_key(state),
+                State::NonTerminal(NonTerminal { state }) => {
+                    !transition_reward_map.contains_key(state)
+                }
+            })
+        });
+        if inconsistent {
+            return Err(Error::InconsistentTerminal);
+        }
+
+        let mut transition_map = HashMap::with_capacity(transition_reward_map.len());
+        for (state, dist) in &transition_reward_map {
+            let transition = marginalize(dist, |(next_state, _)| next_state.state().clone())?;
+            transition_map.insert(state.clone(), transition);
+        }
+
+        Ok(FiniteMarkovRewardProcess {
+            process: FiniteMarkovProcess::new(transition_map),
+            transition_reward_map: transition_reward_map
+                .into_iter()
+                .map(|(state, dist)| (NonTerminal::new(state), dist))
+                .collect(),
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Returns the markov process obtained by marginalizing out the rewards.
+    pub fn markov_process(&self) -> &FiniteMarkovProcess<S, Categorical<S>> {
+        &self.process
+    }
+
+    /// Returns the non-terminal states of this process, in the order used to index
+    /// [`FiniteMarkovRewardProcess::expected_rewards`] and the transition matrix of the
+    /// underlying process.
+    pub fn non_terminal_states(&self) -> &[NonTerminal<S>] {
+        self.process.non_terminal_states()
     }
 
-    fn simulate_iter<D, Z>(self, start_state_dist: D) -> Z
+    /// Returns the number of non-terminal states of this process.
+    pub fn num_states(&self) -> usize {
+        self.process.num_states()
+    }
+
+    /// Returns the numbering of the non-terminal states used to index
+    /// [`FiniteMarkovRewardProcess::expected_rewards`].
+    pub fn state_index(&self) -> StateIndex<'_, S> {
+        self.process.state_index()
+    }
+
+    /// Returns the discounted state-visitation measure d(s) = Σₜ γᵗ P(sₜ = s) of the
+    /// non-terminal states for discount factor `gamma`, when the start state is drawn from
+    /// `start_state_dist`, found by solving the linear system d(I - γP) = μ.
+    ///
+    /// Starting mass on terminal states visits nothing. The measure sums to 1 / (1 - γ)
+    /// times the starting mass on non-terminal states when no mass reaches a terminal
+    /// state, and to less otherwise. Returns [`Error::InvalidParameter`] unless `gamma`
+    /// lies in [0, 1).
+    pub fn discounted_occupancy<D>(
+        &self,
+        start_state_dist: &D,
+        gamma: f64,
+    ) -> Result<HashMap<NonTerminal<S>, f64>>
     where
-        D: Distribution<S>,
-        Z: Iterator<Item = State<S>>,
+        D: FiniteDistribution<S>,
     {
-        todo!()
+        check_discount(gamma, false)?;
+        let index = self.state_index();
+        let (matrix, _) = self.process.get_transition_matrix();
+        let n = index.len();
+
+        let mut a = vec![0.0; n * n];
+        for i in 0..n {
+            for j in 0..n {
+                a[j * n + i] = -gamma * matrix[i * n + j];
+            }
+            a[i * n + i] += 1.0;
+        }
+        let start = index
+            .states()
+            .iter()
+            .map(|state| start_state_dist.probability(&state.state))
+            .collect();
+        let occupancy = linalg::solve(a, start)?;
+
+        Ok(index.states().iter().cloned().zip(occupancy).collect())
     }
 
-    fn traces_iter<D, Y, Z>(self, start_state_dist: D) -> Z
+    /// Returns the expected immediate reward Σ p(s', r) · r of each non-terminal state,
+    /// together with the ordering of states used to index it. The ordering is the same as
+    /// for [`FiniteMarkovProcess::get_transition_matrix`].
+    pub fn expected_rewards(&self) -> (Vec<R>, Vec<&NonTerminal<S>>)
     where
-        D: Distribution<S>,
-        Y: Iterator<Item = State<S>>,
-        Z: Iterator<Item = Y>,
+        R: RewardValue,
     {
-        todo!()
+        let states = &self.process.non_terminal_states;
+        let rewards = states
+            .iter()
+            .map(|state| {
+                self.transition_reward_map[state]
+                    .table()
+                    .iter()
+                    .fold(R::zero(), |total, ((_, reward), &p)| {
+                        total + reward.clone() * p
+                    })
+            })
+            .collect();
+        (rewards, states.iter().collect())
     }
 }
-// --------------------------------------------------------------------------------------
 
-// Trait: `MarkovRewardProcess` =========================================================
-pub trait MarkovRewardProcess<S>: MarkovProcess<S> {
-    fn transition_reward<D>(self, state: NonTerminal<S>) -> D
+impl<S, X> FiniteMarkovRewardProcess<S, X, Reward>
+where
+    S: Ord + Hash + Clone,
+    X: FiniteDistribution<(State<S>, Reward)>,
+{
+    /// Returns the expected immediate reward R(s) = Σ p(s', r) · r of each non-terminal
+    /// state as a real number, ordered as for [`FiniteMarkovRewardProcess::expected_rewards`].
+    pub fn reward_function(&self) -> (Vec<f64>, Vec<&NonTerminal<S>>) {
+        let (rewards, states) = self.expected_rewards();
+        (rewards.into_iter().map(|reward| reward.0).collect(), states)
+    }
+
+    /// Returns the reward process with the same transitions as this one, but with every
+    /// reward clamped into the interval [`low`, `high`]. Outcomes that differ only in
+    /// rewards clamped to the same bound are merged.
+    ///
+    /// Infinite bounds leave that side unclipped. Returns [`Error::InvalidParameter`] if
+    /// either bound is NaN or `high < low`.
+    pub fn clip_rewards(
+        &self,
+        low: f64,
+        high: f64,
+    ) -> Result<FiniteMarkovRewardProcess<S, StateReward<S>>> {
+        if low.is_nan() {
+            return Err(Error::InvalidParameter {
+                name: "low",
+                value: low,
+            });
+        }
+        if high.is_nan() || high < low {
+            return Err(Error::InvalidParameter {
+                name: "high",
+                value: high,
+            });
+        }
+
+        let mut transition_reward_map = HashMap::with_capacity(self.transition_reward_map.len());
+        for (state, dist) in &self.transition_reward_map {
+            let clipped = marginalize(dist, |(next_state, reward)| {
+                (next_state.clone(), Reward(reward.0.clamp(low, high)))
+            })?;
+            transition_reward_map.insert(state.state().clone(), clipped);
+        }
+        FiniteMarkovRewardProcess::new(transition_reward_map)
+    }
+
+    /// Returns the reward process with the same transitions as this one, but with each
+    /// reward r of a transition from s to s' replaced by the potential-based shaped reward
+    /// r + γΦ(s') - Φ(s) for the potential Φ = `potential` and discount factor `gamma`.
+    ///
+    /// If Φ is zero on terminal states, the value function of the shaped process is
+    /// V(s) - Φ(s), so shaping a decision process this way leaves its optimal policies
+    /// unchanged. Propagates the errors of [`FiniteMarkovRewardProcess::new`].
+    pub fn shape_rewards<Phi>(
+        &self,
+        gamma: f64,
+        potential: Phi,
+    ) -> Result<FiniteMarkovRewardProcess<S, StateReward<S>>>
+    where
+        Phi: Fn(&State<S>) -> f64,
+    {
+        let mut transition_reward_map = HashMap::with_capacity(self.transition_reward_map.len());
+        for (state, dist) in &self.transition_reward_map {
+            let phi = potential(&State::NonTerminal(state.clone()));
+            let shaped = marginalize(dist, |(next_state, reward)| {
+                let shaped = reward.0 + gamma * potential(next_state) - phi;
+                (next_state.clone(), Reward(shaped))
+            })?;
+            transition_reward_map.insert(state.state().clone(), shaped);
+        }
+        FiniteMarkovRewardProcess::new(transition_reward_map)
+    }
+
+    /// Returns a Graphviz DOT digraph of this process, as for
+    /// [`FiniteMarkovProcess::to_dot`], with each edge also labelled by the expected reward
+    /// of its transition.
+    pub fn to_dot(&self) -> String
+    where
+        S: Display,
+    {
+        self.process.to_dot_with(|from, to, p| {
+            let reward: f64 = self.transition_reward_map[from]
+                .table()
+                .iter()
+                .filter(|((next_state, _), _)| next_state.state() == to)
+                .map(|((_, reward), &q)| q * reward.0)
+                .sum();
+            format!("{:.3}, r = {:.3}", p, reward / p)
+        })
+    }
+
+    /// Returns lower and upper bounds on the value of every state for discount factor
+    /// `gamma` in [0, 1], from the smallest and largest rewards r_min and r_max of any
+    /// transition with non-zero probability.
+    ///
+    /// A trace collects between one reward and infinitely many, so the bounds are
+    /// min(r_min, r_min / (1 - γ)) and max(r_max, r_max / (1 - γ)): a positive r_min only
+    /// bounds the value below by r_min, since the trace may end after one step. For
+    /// `gamma = 1` a negative r_min or a positive r_max gives an infinite bound. Both
+    /// bounds are zero for a process without non-terminal states.
+    ///
+    /// Returns [`Error::InvalidParameter`] unless `gamma` lies in [0, 1].
+    pub fn value_bounds(&self, gamma: f64) -> Result<(f64, f64)> {
+        check_discount(gamma, true)?;
+        let mut rewards = self
+            .transition_reward_map
+            .values()
+            .flat_map(|dist| dist.table())
+            .filter(|(_, &p)| p > 0.0)
+            .map(|((_, reward), _)| reward.0)
+            .peekable();
+        if rewards.peek().is_none() {
+            return Ok((0.0, 0.0));
+        }
+        let (r_min, r_max) = rewards.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), r| {
+            (lo.min(r), hi.max(r))
+        });
+
+        let discounted = |r: f64| {
+            if r == 0.0 {
+                0.0
+            } else if gamma < 1.0 {
+                r / (1.0 - gamma)
+            } else {
+                r * f64::INFINITY
+            }
+        };
+        Ok((r_min.min(discounted(r_min)), r_max.max(discounted(r_max))))
+    }
+
+    /// Returns the value function of this process for discount factor `gamma`, found by
+    /// solving the Bellman equation V = R + γPV exactly as V = (I - γP)⁻¹R.
+    ///
+    /// Returns [`Error::InvalidParameter`] unless `gamma` lies in [0, 1): for γ = 1 the
+    /// value is only guaranteed to be finite over a finite horizon, as in
+    /// [`crate::dynamic_programming::finite_horizon_value_iteration`]. Returns
+    /// [`Error::SingularMatrix`] if `I - γP` is singular.
+    pub fn value_function(&self, gamma: f64) -> Result<ValueFunction<S>> {
+        check_discount(gamma, false)?;
+        let index = self.state_index();
+        let (matrix, _) = self.process.get_transition_matrix();
+        let (rewards, _) = self.reward_function();
+        let n = index.len();
+
+        let mut a: Vec<f64> = matrix.into_iter().map(|p| -gamma * p).collect();
+        for i in 0..n {
+            a[i * n + i] += 1.0;
+        }
+        let values = linalg::solve(a, rewards)?;
+
+        Ok(index.states().iter().cloned().zip(values).collect())
+    }
+
+    /// Returns the value function of this process for discount factor `gamma`, found by
+    /// repeatedly applying the Bellman operator V ← R + γPV, together with a report of how
+    /// the iteration stopped under `convergence`.
+    ///
+    /// The iteration starts from `initial`, with states missing from it at zero, or from
+    /// V = 0 if it is `None`. A process without non-terminal states has an empty value
+    /// function and takes no iterations.
+    ///
+    /// Returns [`Error::InvalidParameter`] unless `gamma` lies in [0, 1), as for
+    /// [`FiniteMarkovRewardProcess::value_function`], since otherwise the iteration need
+    /// not converge.
+    pub fn value_function_iterative(
+        &self,
+        gamma: f64,
+        convergence: Convergence,
+        initial: Option<&ValueFunction<S>>,
+    ) -> Result<(ValueFunction<S>, ConvergenceReport)> {
+        check_discount(gamma, false)?;
+        let index = self.state_index();
+        let (matrix, _) = self.process.get_transition_matrix();
+        let (rewards, _) = self.reward_function();
+        let n = index.len();
+        if n == 0 {
+            return Ok((HashMap::new(), convergence.report(0, 0.0)));
+        }
+
+        let mut values: Vec<f64> = index
+            .states()
+            .iter()
+            .map(|s| initial.and_then(|vf| vf.get(s)).copied().unwrap_or(0.0))
+            .collect();
+        let mut iterations = 0;
+        let mut delta = f64::INFINITY;
+        while iterations < convergence.max_iters {
+            let next: Vec<f64> = matrix
+                .chunks(n)
+                .zip(&rewards)
+                .map(|(row, r)| {
+                    r + gamma * row.iter().zip(&values).map(|(p, v)| p * v).sum::<f64>()
+                })
+                .collect();
+            delta = next
+                .iter()
+                .zip(&values)
+                .map(|(a, b)| (a - b).abs())
+                .fold(0.0, f64::max);
+            values = next;
+            iterations += 1;
+
+            if convergence.accepts(delta) {
+                break;
+            }
+        }
+
+        Ok((
+            index.states().iter().cloned().zip(values).collect(),
+            convergence.report(iterations, delta),
+        ))
+    }
+}
+
+/// Serialized as a sequence of `(state, transition_reward)` pairs, since states need not
+/// be valid map keys in formats such as JSON.
+#[cfg(feature = "serde")]
+impl<S, X, R> serde::Serialize for FiniteMarkovRewardProcess<S, X, R>
+where
+    S: Ord + Hash + Clone + serde::Serialize,
+    R: Eq + Hash,
+    X: FiniteDistribution<(State<S>, R)> + serde::Serialize,
+{
+    fn serialize<Ser>(&self, serializer: Ser) -> std::result::Result<Ser::Ok, Ser::Error>
+    where
+        Ser: serde::Serializer,
+    {
+        serializer.collect_seq(
+            self.process
+                .non_terminal_states
+                .iter()
+                .map(|state| (&state.state, &self.transition_reward_map[state])),
+        )
+    }
+}
+
+/// Deserialized through [`FiniteMarkovRewardProcess::new`], which derives the underlying
+/// markov process again.
+#[cfg(feature = "serde")]
+impl<'de, S, X, R> serde::Deserialize<'de> for FiniteMarkovRewardProcess<S, X, R>
+where
+    S: Ord + Hash + Clone + serde::Deserialize<'de>,
+    R: Eq + Hash,
+    X: FiniteDistribution<(State<S>, R)> + serde::Deserialize<'de>,
+{
+    fn deserialize<De>(deserializer: De) -> std::result::Result<Self, De::Error>
+    where
+        De: serde::Deserializer<'de>,
+    {
+        let pairs: Vec<(S, X)> = serde::Deserialize::deserialize(deserializer)?;
+        FiniteMarkovRewardProcess::new(pairs.into_iter().collect())
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl<S, X, R> MarkovProcess<S> for FiniteMarkovRewardProcess<S, X, R>
+where
+    S: Ord + Hash + Clone,
+    R: Eq + Hash,
+    X: FiniteDistribution<(State<S>, R)>,
+{
+    type Transition = Categorical<S>;
+
+    fn transition(&self, state: &NonTerminal<S>) -> Option<&Categorical<S>> {
+        self.process.transition(state)
+    }
+}
+
+impl<S, X, R> MarkovRewardProcess<S, R> for FiniteMarkovRewardProcess<S, X, R>
+where
+    S: Ord + Hash + Clone,
+    R: Eq + Hash,
+    X: FiniteDistribution<(State<S>, R)>,
+{
+    type TransitionReward = X;
+
+    fn transition_reward(&self, state: &NonTerminal<S>) -> Option<&X> {
+        self.transition_reward_map.get(state)
+    }
+
+    /// The expected reward is computed exactly from the table of the transition.
+    fn expected_reward(&self, state: &NonTerminal<S>) -> Option<f64>
     where
-        D: Distribution<(State<S>, f64)>;
+        R: Clone + Into<f64>,
+    {
+        let transition = self.transition_reward_map.get(state)?;
+        Some(FiniteDistribution::expectation(
+            transition,
+            |(_, reward)| reward.clone().into(),
+        ))
+    }
 }
 
 // --------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distribution::{total_variation, Categorical, Constant};
+
+    /// A process on states 0, 1, ..., `n - 1` that moves from each state to the next, and
+    /// from the last one to the terminal state `n`.
+    fn line(n: u32) -> FiniteMarkovProcess<u32, Constant<u32>> {
+        FiniteMarkovProcess::new((0..n).map(|s| (s, Constant::new(s + 1))).collect())
+    }
+
+    /// A process on states 0 and 1 that moves from each state to the other or to the
+    /// terminal state 2 with equal probability.
+    fn walk() -> FiniteMarkovProcess<u32, Categorical<u32>> {
+        let outcomes =
+            |other: u32| Categorical::new(HashMap::from([(other, 0.5), (2, 0.5)])).unwrap();
+        FiniteMarkovProcess::new(HashMap::from([(0, outcomes(1)), (1, outcomes(0))]))
+    }
+
+    #[test]
+    fn transition_matrix_omits_terminal_mass() {
+        let p = |from: u32, to: u32| match (from, to) {
+            (0, 1) | (0, 2) => 0.5,
+            (1, 2) => 0.25,
+            (1, 3) => 0.75,
+            (2, 0) => 1.0,
+            _ => 0.0,
+        };
+        let transitions = (0..3)
+            .map(|from| {
+                let successors = (0..4).map(|to| (to, p(from, to))).filter(|&(_, q)| q > 0.0);
+                (from, Categorical::new(successors.collect()).unwrap())
+            })
+            .collect();
+        let process = FiniteMarkovProcess::new(transitions);
+
+        let (matrix, states) = process.get_transition_matrix();
+        assert_eq!(states.len(), 3);
+        assert_eq!(matrix.len(), 9);
+        for (i, from) in states.iter().enumerate() {
+            for (j, to) in states.iter().enumerate() {
+                assert_eq!(matrix[i * 3 + j], p(from.state, to.state));
+            }
+        }
+        let row_sums: Vec<f64> = matrix.chunks(3).map(|row| row.iter().sum()).collect();
+        let one = states.iter().position(|s| s.state == 1).unwrap();
+        assert_eq!(row_sums[one], 0.25);
+    }
+
+    #[test]
+    fn stationary_distribution_of_two_state_chain() {
+        // With P = [[1 - a, a], [b, 1 - b]], π = (b, a) / (a + b).
+        let (a, b) = (0.3, 0.1);
+        let process = FiniteMarkovProcess::new(HashMap::from([
+            (
+                0,
+                Categorical::new(HashMap::from([(0, 1.0 - a), (1, a)])).unwrap(),
+            ),
+            (
+                1,
+                Categorical::new(HashMap::from([(0, b), (1, 1.0 - b)])).unwrap(),
+            ),
+        ]));
+        let pi = process.get_stationary_distribution().unwrap();
+        assert!((pi.probability(&0) - b / (a + b)).abs() < 1e-9);
+        assert!((pi.probability(&1) - a / (a + b)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn stationary_distribution_of_periodic_chain() {
+        let process = FiniteMarkovProcess::new(HashMap::from([
+            (0, Constant::new(1)),
+            (1, Constant::new(0)),
+        ]));
+        let pi = process.get_stationary_distribution().unwrap();
+        assert!((pi.probability(&0) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn no_stationary_distribution_for_reducible_or_leaking_chains() {
+        let reducible = FiniteMarkovProcess::new(HashMap::from([
+            (0, Constant::new(0)),
+            (1, Constant::new(1)),
+        ]));
+        assert!(reducible.get_stationary_distribution().is_none());
+        assert!(line(2).get_stationary_distribution().is_none());
+    }
+
+    #[test]
+    fn simulated_trace_ends_at_terminal_state() {
+        let trace: Vec<State<u32>> = walk()
+            .simulate_iter_with(Constant::new(0), StdRng::seed_from_u64(1))
+            .take(1_000)
+            .collect();
+
+        let (last, rest) = trace.split_last().unwrap();
+        assert!(matches!(last, State::Terminal(s) if s.state == 2));
+        assert!(matches!(rest[0], State::NonTerminal(s) if s.state == 0));
+        assert!(rest.iter().all(|s| matches!(s, State::NonTerminal(_))));
+    }
+
+    #[test]
+    fn seeded_traces_are_reproducible() {
+        let process = walk();
+        let trace = |seed| -> Vec<u32> {
+            process
+                .simulate_iter_with(Constant::new(0), StdRng::seed_from_u64(seed))
+                .map(|state| match state {
+                    State::Terminal(s) => s.state,
+                    State::NonTerminal(s) => s.state,
+                })
+                .collect()
+        };
+        assert_eq!(trace(9), trace(9));
+        assert!((0..20).map(trace).any(|t| t.len() > 2));
+    }
+
+    #[test]
+    fn traces_start_from_the_start_state_distribution() {
+        let process = walk();
+        let start = Categorical::new(HashMap::from([(0, 0.5), (1, 0.5)])).unwrap();
+        let traces: Vec<Vec<State<u32>>> = process
+            .traces_iter_with(start, StdRng::seed_from_u64(4))
+            .take(50)
+            .map(Iterator::collect)
+            .collect();
+
+        assert_eq!(traces.len(), 50);
+        let mut starts: Vec<u32> = traces
+            .iter()
+            .map(|trace| match trace[0] {
+                State::NonTerminal(s) => s.state,
+                State::Terminal(_) => panic!("trace starts at a terminal state"),
+            })
+            .collect();
+        starts.sort();
+        starts.dedup();
+        assert_eq!(starts, vec![0, 1]);
+        assert!(traces
+            .iter()
+            .all(|trace| matches!(trace.last(), Some(State::Terminal(s)) if s.state == 2)));
+    }
+
+    #[test]
+    fn seeded_batches_of_traces_are_reproducible() {
+        let process = walk();
+        let batch = |seed| -> Vec<usize> {
+            process
+                .traces_iter_with(Constant::new(0), StdRng::seed_from_u64(seed))
+                .take(10)
+                .map(Iterator::count)
+                .collect()
+        };
+        assert_eq!(batch(6), batch(6));
+    }
+
+    /// A reward process on states 0 and 1 that moves from each state to the other or to
+    /// the terminal state 2 with equal probability, with reward 1 for every transition.
+    fn chain() -> FiniteMarkovRewardProcess<u32, Categorical<(State<u32>, Reward)>> {
+        let outcomes = |other: u32| {
+            Categorical::new(HashMap::from([
+                (
+                    (State::NonTerminal(NonTerminal::new(other)), Reward(1.0)),
+                    0.5,
+                ),
+                ((State::Terminal(Terminal::new(2)), Reward(1.0)), 0.5),
+            ]))
+            .unwrap()
+        };
+        FiniteMarkovRewardProcess::new(HashMap::from([(0, outcomes(1)), (1, outcomes(0))])).unwrap()
+    }
+
+    #[test]
+    fn states_round_trip_as_map_keys() {
+        let terminal = State::Terminal(Terminal::new(1));
+        let non_terminal = State::NonTerminal(NonTerminal::new(1));
+        assert_ne!(terminal, non_terminal);
+
+        let values = HashMap::from([(terminal, 0.25), (non_terminal, 0.75)]);
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[&State::Terminal(Terminal::new(1))], 0.25);
+        assert_eq!(values[&State::NonTerminal(NonTerminal::new(1))], 0.75);
+        assert!(!values.contains_key(&State::NonTerminal(NonTerminal::new(2))));
+    }
+
+    #[test]
+    fn marginalizes_rewards_into_transitions() {
+        let mrp = chain();
+        let transition = mrp.transition(&NonTerminal::new(0)).unwrap();
+        assert_eq!(transition.probability(&1), 0.5);
+        assert_eq!(transition.probability(&2), 0.5);
+        assert!(mrp.transition_reward(&NonTerminal::new(2)).is_none());
+    }
+
+    #[test]
+    fn rejects_terminal_successor_that_is_a_state() {
+        let dist = Categorical::new(HashMap::from([(
+            (State::Terminal(Terminal::new(1)), Reward(0.0)),
+            1.0,
+        )]))
+        .unwrap();
+        let result = FiniteMarkovRewardProcess::new(HashMap::from([(0, dist.clone()), (1, dist)]));
+        assert_eq!(result.err(), Some(Error::InconsistentTerminal));
+    }
+
+    #[test]
+    fn rejects_non_terminal_successor_that_is_not_a_state() {
+        let dist = Categorical::new(HashMap::from([(
+            (State::NonTerminal(NonTerminal::new(1)), Reward(0.0)),
+            1.0,
+        )]))
+        .unwrap();
+        let result = FiniteMarkovRewardProcess::new(HashMap::from([(0, dist)]));
+        assert_eq!(result.err(), Some(Error::InconsistentTerminal));
+    }
+
+    #[test]
+    fn reward_function_averages_rewards_over_outcomes() {
+        let step = |outcomes: &[(State<u32>, f64, f64)]| {
+            let table = outcomes.iter().map(|&(s, r, p)| ((s, Reward(r)), p));
+            Categorical::new(table.collect()).unwrap()
+        };
+        let mrp = FiniteMarkovRewardProcess::new(HashMap::from([
+            (
+                0,
+                step(&[
+                    (State::NonTerminal(NonTerminal::new(1)), 2.0, 0.25),
+                    (State::Terminal(Terminal::new(2)), -1.0, 0.75),
+                ]),
+            ),
+            (
+                1,
+                step(&[
+                    (State::NonTerminal(NonTerminal::new(0)), 4.0, 0.5),
+                    (State::NonTerminal(NonTerminal::new(1)), 0.0, 0.5),
+                ]),
+            ),
+        ]))
+        .unwrap();
+
+        let (rewards, states) = mrp.reward_function();
+        assert_eq!(states, mrp.markov_process().get_transition_matrix().1);
+        for (reward, state) in rewards.iter().zip(states) {
+            // 0.25 * 2 - 0.75 * 1 and 0.5 * 4 + 0.5 * 0.
+            let expected = if state.state == 0 { -0.25 } else { 2.0 };
+            assert!((reward - expected).abs() < 1e-12);
+            assert!((mrp.expected_reward(state).unwrap() - reward).abs() < 1e-12);
+        }
+        assert_eq!(mrp.expected_reward(&NonTerminal::new(2)), None);
+    }
+
+    #[test]
+    fn value_function_matches_closed_form() {
+        // By symmetry V(0) = V(1) = V, with V = 1 + γ V / 2.
+        let gamma = 0.9;
+        let vf = chain().value_function(gamma).unwrap();
+        let expected = 1.0 / (1.0 - gamma / 2.0);
+        assert_eq!(vf.len(), 2);
+        for value in vf.values() {
+            assert!((value - expected).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn value_function_of_self_loop() {
+        // A process that never terminates has no finite value for γ = 1, where I - γP
+        // would be singular, so that discount is rejected up front.
+        let mrp = FiniteMarkovRewardProcess::new(HashMap::from([(
+            0,
+            Constant::new((State::NonTerminal(NonTerminal::new(0)), Reward(1.0))),
+        )]))
+        .unwrap();
+        assert!(matches!(
+            mrp.value_function(1.0),
+            Err(Error::InvalidParameter { name: "gamma", .. })
+        ));
+        let vf = mrp.value_function(0.5).unwrap();
+        assert!((vf[&NonTerminal::new(0)] - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn solve_reports_singular_matrix() {
+        let result = linalg::solve(vec![1.0, 2.0, 2.0, 4.0], vec![1.0, 1.0]);
+        assert_eq!(result.err(), Some(Error::SingularMatrix));
+    }
+
+    #[test]
+    fn iterative_value_function_matches_exact_solve() {
+        let mrp = chain();
+        let exact = mrp.value_function(0.9).unwrap();
+        let (iterative, report) = mrp
+            .value_function_iterative(0.9, Convergence::new(1e-10, 1_000), None)
+            .unwrap();
+        assert!(report.converged);
+        assert!(report.iterations < 1_000);
+        for (state, value) in &exact {
+            assert!((iterative[state] - value).abs() < 1e-8);
+        }
+    }
+
+    #[test]
+    fn iterative_value_function_of_empty_process_is_empty() {
+        let mrp: FiniteMarkovRewardProcess<u32, Categorical<(State<u32>, Reward)>> =
+            FiniteMarkovRewardProcess::new(HashMap::new()).unwrap();
+        let (vf, report) = mrp
+            .value_function_iterative(0.9, Convergence::new(1e-6, 100), None)
+            .unwrap();
+        assert!(vf.is_empty());
+        assert_eq!(report.iterations, 0);
+        assert!(report.converged);
+    }
+
+    #[test]
+    fn absorbing_states_cannot_reach_a_terminal_state() {
+        let process = line(3);
+        assert!(process.absorbing_states().is_empty());
+        assert!((0..3).all(|s| process.is_terminal_reachable(&NonTerminal::new(s))));
+
+        // 0 may stay put or move to 1, which never leaves; 2 may end at the terminal 3.
+        let process = FiniteMarkovProcess::new(HashMap::from([
+            (
+                0,
+                Categorical::new(HashMap::from([(0, 1.0), (1, 1.0)])).unwrap(),
+            ),
+            (1, Categorical::new(HashMap::from([(1, 1.0)])).unwrap()),
+            (
+                2,
+                Categorical::new(HashMap::from([(0, 1.0), (3, 1.0)])).unwrap(),
+            ),
+        ]));
+        assert_eq!(process.absorbing_states(), vec![&NonTerminal::new(1)]);
+        assert!(!process.is_terminal_reachable(&NonTerminal::new(0)));
+        assert!(!process.is_terminal_reachable(&NonTerminal::new(1)));
+        assert!(process.is_terminal_reachable(&NonTerminal::new(2)));
+    }
+
+    #[test]
+    fn dot_has_one_edge_per_transition() {
+        let dot = line(3).to_dot();
+        assert_eq!(dot.lines().filter(|line| line.contains("->")).count(), 3);
+        assert!(dot.contains("\"2\" -> \"3\" [label=\"1.000\"];"));
+        assert!(dot.contains("\"3\" [shape=doublecircle];"));
+
+        let dot = chain().to_dot();
+        assert_eq!(dot.lines().filter(|line| line.contains("->")).count(), 4);
+        assert!(dot.contains("\"0\" -> \"1\" [label=\"0.500, r = 1.000\"];"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn processes_round_trip_through_json() {
+        let mrp = chain();
+        let process = mrp.markov_process();
+        let json = serde_json::to_string(process).unwrap();
+        let restored: FiniteMarkovProcess<u32, Categorical<u32>> =
+            serde_json::from_str(&json).unwrap();
+        let (_, states) = process.get_transition_matrix();
+        assert_eq!(restored.get_transition_matrix().1.len(), states.len());
+        for state in states {
+            let expected = process.transition(state).unwrap().table();
+            assert_eq!(restored.transition(state).unwrap().table(), expected);
+        }
+
+        let json = serde_json::to_string(&mrp).unwrap();
+        let restored: FiniteMarkovRewardProcess<u32, StateReward<u32>> =
+            serde_json::from_str(&json).unwrap();
+        let (_, states) = mrp.markov_process().get_transition_matrix();
+        let (_, restored_states) = restored.markov_process().get_transition_matrix();
+        assert_eq!(restored_states.len(), states.len());
+        for state in states {
+            let expected = mrp.transition_reward(state).unwrap().table();
+            assert_eq!(restored.transition_reward(state).unwrap().table(), expected);
+        }
+    }
+
+    #[test]
+    fn reward_type_is_generic() {
+        // The common real-valued case, with the reward type left to its default.
+        let mrp: FiniteMarkovRewardProcess<u32, StateReward<u32>> = chain();
+        let (rewards, _) = mrp.expected_rewards();
+        assert_eq!(rewards, vec![Reward(1.0), Reward(1.0)]);
+
+        // A two-objective reward.
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        struct Pair(Reward, Reward);
+        impl Add for Pair {
+            type Output = Pair;
+            fn add(self, other: Pair) -> Pair {
+                Pair(self.0 + other.0, self.1 + other.1)
+            }
+        }
+        impl Mul<f64> for Pair {
+            type Output = Pair;
+            fn mul(self, factor: f64) -> Pair {
+                Pair(self.0 * factor, self.1 * factor)
+            }
+        }
+        impl RewardValue for Pair {
+            fn zero() -> Self {
+                Pair(Reward(0.0), Reward(0.0))
+            }
+        }
+
+        let outcomes = Categorical::new(HashMap::from([
+            (
+                (
+                    State::Terminal(Terminal::new(1)),
+                    Pair(Reward(2.0), Reward(0.0)),
+                ),
+                0.25,
+            ),
+            (
+                (
+                    State::NonTerminal(NonTerminal::new(0)),
+                    Pair(Reward(0.0), Reward(4.0)),
+                ),
+                0.75,
+            ),
+        ]))
+        .unwrap();
+        let mrp = FiniteMarkovRewardProcess::new(HashMap::from([(0, outcomes)])).unwrap();
+        let (rewards, _) = mrp.expected_rewards();
+        assert_eq!(rewards, vec![Pair(Reward(0.5), Reward(3.0))]);
+    }
+
+    #[test]
+    fn state_helpers_distinguish_terminal_states() {
+        let terminal: State<u32> = State::Terminal(Terminal::new(3));
+        let non_terminal: State<u32> = State::NonTerminal(NonTerminal::new(4));
+
+        assert!(terminal.is_terminal() && !terminal.is_non_terminal());
+        assert!(non_terminal.is_non_terminal() && !non_terminal.is_terminal());
+        assert_eq!(*terminal.state(), 3);
+        assert_eq!(*non_terminal.state(), 4);
+
+        assert_eq!(terminal.on_terminal(|s| s.state() * 10, 0), 30);
+        assert_eq!(non_terminal.on_terminal(|s| s.state() * 10, 0), 0);
+        assert_eq!(terminal.on_non_terminal(|s| s.state() * 10, 0), 0);
+        assert_eq!(non_terminal.on_non_terminal(|s| s.state() * 10, 0), 40);
+    }
+
+    #[test]
+    fn simulated_stationary_estimate_approaches_the_exact_one() {
+        let (a, b) = (0.3, 0.1);
+        let process = FiniteMarkovProcess::new(HashMap::from([
+            (
+                0,
+                Categorical::new(HashMap::from([(0, 1.0 - a), (1, a)])).unwrap(),
+            ),
+            (
+                1,
+                Categorical::new(HashMap::from([(0, b), (1, 1.0 - b)])).unwrap(),
+            ),
+        ]));
+        let exact = process.get_stationary_distribution().unwrap();
+        let error = |num_steps: usize| {
+            let rng = StdRng::seed_from_u64(69);
+            let estimate = process
+                .estimate_stationary_distribution_with(Constant::new(0), num_steps, 100, rng)
+                .unwrap();
+            total_variation(&estimate, &exact)
+        };
+        let errors: Vec<f64> = [100, 10_000, 200_000].into_iter().map(error).collect();
+        assert!(errors[2] < errors[0], "{errors:?}");
+        assert!(errors[2] < 0.02, "{errors:?}");
+    }
+
+    #[test]
+    fn clipped_rewards_lie_within_the_bounds() {
+        let outcome =
+            |next_state: State<u32>, reward: f64, p: f64| ((next_state, Reward(reward)), p);
+        let stay = State::NonTerminal(NonTerminal::new(0));
+        let end = State::Terminal(Terminal::new(1));
+        let mrp = FiniteMarkovRewardProcess::new(HashMap::from([(
+            0,
+            Categorical::new(HashMap::from([
+                outcome(stay, -5.0, 0.2),
+                outcome(stay, 0.5, 0.3),
+                outcome(end, 7.0, 0.2),
+                outcome(end, 3.0, 0.3),
+            ]))
+            .unwrap(),
+        )]))
+        .unwrap();
+
+        let clipped = mrp.clip_rewards(-1.0, 1.0).unwrap();
+        let dist = clipped.transition_reward(&NonTerminal::new(0)).unwrap();
+        assert!(dist
+            .table()
+            .keys()
+            .all(|(_, reward)| (-1.0..=1.0).contains(&reward.0)));
+        // The two terminal outcomes are merged, and the terminal state stays terminal.
+        assert_eq!(dist.table().len(), 3);
+        assert!((dist.probability(&(end, Reward(1.0))) - 0.5).abs() < 1e-12);
+        assert_eq!(
+            clipped.transition(&NonTerminal::new(0)).unwrap().table(),
+            mrp.transition(&NonTerminal::new(0)).unwrap().table()
+        );
+
+        assert!(mrp.clip_rewards(1.0, -1.0).is_err());
+        assert!(mrp.clip_rewards(f64::NAN, 1.0).is_err());
+    }
+
+    #[test]
+    fn iterative_value_function_rejects_invalid_discount() {
+        let mrp = chain();
+        for gamma in [1.0, -0.1, f64::NAN] {
+            let result = mrp.value_function_iterative(gamma, Convergence::new(1e-6, 100), None);
+            assert!(matches!(
+                result,
+                Err(Error::InvalidParameter { name: "gamma", .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn transition_matrix_does_not_depend_on_map_order() {
+        let first = line(32);
+        let second = line(32);
+        let (matrix, states) = first.get_transition_matrix();
+        assert_eq!(second.get_transition_matrix(), (matrix, states));
+    }
+
+    #[test]
+    fn state_accessors_follow_the_transition_matrix_order() {
+        let process = line(5);
+        let (matrix, states) = process.get_transition_matrix();
+        assert_eq!(process.num_states(), 5);
+        assert_eq!(matrix.len(), 25);
+        assert_eq!(
+            states,
+            process.non_terminal_states().iter().collect::<Vec<_>>()
+        );
+        // Row i moves to the state after the one at position i.
+        for (i, from) in states.iter().enumerate() {
+            for (j, to) in states.iter().enumerate() {
+                let expected = if to.state == from.state + 1 { 1.0 } else { 0.0 };
+                assert_eq!(matrix[i * 5 + j], expected);
+            }
+        }
+
+        let mrp = chain();
+        let (_, reward_states) = mrp.reward_function();
+        assert_eq!(mrp.num_states(), 2);
+        assert_eq!(
+            reward_states,
+            mrp.non_terminal_states().iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn exact_values_lie_within_the_value_bounds() {
+        let mut rng = StdRng::seed_from_u64(94);
+        let n = 6;
+        let map = (0..n)
+            .map(|s| {
+                let outcomes = (0..=n)
+                    .map(|next| {
+                        let next_state = if next == n {
+                            State::Terminal(Terminal::new(next))
+                        } else {
+                            State::NonTerminal(NonTerminal::new(next))
+                        };
+                        let reward = Reward(rng.gen_range(-3.0..5.0));
+                        ((next_state, reward), rng.gen_range(0.1..1.0))
+                    })
+                    .collect();
+                (s, Categorical::new(outcomes).unwrap())
+            })
+            .collect();
+        let mrp = FiniteMarkovRewardProcess::new(map).unwrap();
+
+        for gamma in [0.0, 0.5, 0.9, 0.99] {
+            let (low, high) = mrp.value_bounds(gamma).unwrap();
+            assert!(low < 0.0 && high > 0.0);
+            for value in mrp.value_function(gamma).unwrap().values() {
+                assert!(
+                    low - 1e-9 <= *value && *value <= high + 1e-9,
+                    "{value} outside [{low}, {high}]"
+                );
+            }
+        }
+        assert!(mrp.value_bounds(-0.1).is_err());
+        assert!(mrp.value_bounds(1.5).is_err());
+        assert!(mrp.value_bounds(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn state_index_round_trips() {
+        let process = line(32);
+        let index = process.state_index();
+        assert_eq!(index.len(), 32);
+        assert_eq!(index.states(), process.non_terminal_states());
+        for i in 0..index.len() {
+            assert_eq!(index.index_of(index.state_at(i)), Some(i));
+        }
+        assert_eq!(index.index_of(&NonTerminal::new(32)), None);
+        assert_eq!(line(32).state_index().states(), index.states());
+    }
+
+    #[test]
+    fn discounted_occupancy_sums_to_discounted_start_mass() {
+        let flip =
+            |other: u32| Constant::new((State::NonTerminal(NonTerminal::new(other)), Reward(0.0)));
+        let closed =
+            FiniteMarkovRewardProcess::new(HashMap::from([(0, flip(1)), (1, flip(0))])).unwrap();
+        // State 5 is not a state of either process, so only 0.8 of the mass starts in one.
+        let start = Categorical::new(HashMap::from([(0, 0.5), (1, 0.3), (5, 0.2)])).unwrap();
+        let gamma = 0.9;
+
+        let occupancy = closed.discounted_occupancy(&start, gamma).unwrap();
+        let total: f64 = occupancy.values().sum();
+        assert!((total - 0.8 / (1.0 - gamma)).abs() < 1e-9);
+
+        let leaking: f64 = chain()
+            .discounted_occupancy(&start, gamma)
+            .unwrap()
+            .values()
+            .sum();
+        // Half of the mass leaks into the terminal state on each step.
+        assert!((leaking - 0.8 / (1.0 - gamma / 2.0)).abs() < 1e-9);
+        assert!(matches!(
+            closed.discounted_occupancy(&start, 1.0),
+            Err(Error::InvalidParameter { name: "gamma", .. })
+        ));
+    }
+}
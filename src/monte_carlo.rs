@@ -0,0 +1,359 @@
+#[cfg(feature = "parallel")]
+use crate::distribution::Distribution;
+use crate::distribution::FiniteDistribution;
+use crate::markov_decision_process::ActionValueFunction;
+#[cfg(feature = "parallel")]
+use crate::markov_process::MarkovRewardProcess;
+use crate::markov_process::{NonTerminal, State, ValueFunction};
+use crate::policy::{greedy_action, DeterministicPolicy, Policy};
+use crate::returns::discounted_returns;
+#[cfg(feature = "parallel")]
+use rand::{rngs::StdRng, SeedableRng};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+// Enum: `VisitType` ====================================================================
+
+/// Which visits to a state within an episode contribute a return to its estimate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum VisitType {
+    /// Only the first visit to a state in each episode.
+    First,
+    /// Every visit to a state.
+    Every,
+}
+
+// --------------------------------------------------------------------------------------
+
+// [ Prediction ] =======================================================================
+
+/// Returns a Monte Carlo estimate of the value function of a reward process from sampled
+/// episodes.
+///
+/// Each episode is a sequence of `(state, reward)` steps, where `reward` is the reward
+/// received on leaving `state`, ending with the transition into a terminal state. The
+/// estimate for each state is the average of the discounted returns that follow its visits:
+/// only the first visit to it in each episode, or every visit, according to `visits`.
+pub fn mc_prediction<S, I, E>(traces: I, gamma: f64, visits: VisitType) -> ValueFunction<S>
+where
+    S: Ord + Hash + Clone,
+    I: IntoIterator<Item = E>,
+    E: IntoIterator<Item = (NonTerminal<S>, f64)>,
+{
+    let mut totals = HashMap::new();
+    for trace in traces {
+        let (states, rewards): (Vec<NonTerminal<S>>, Vec<f64>) = trace.into_iter().unzip();
+        accumulate_returns(&mut totals, states, &rewards, gamma, visits);
+    }
+    average_returns(totals)
+}
+
+/// Sums of returns and visit counts for each state.
+type ReturnTotals<S> = HashMap<NonTerminal<S>, (f64, usize)>;
+
+/// Adds the discounted returns that follow the visits to `states` in one episode to
+/// `totals`, counting the visits selected by `visits`.
+fn accumulate_returns<S>(
+    totals: &mut ReturnTotals<S>,
+    states: Vec<NonTerminal<S>>,
+    rewards: &[f64],
+    gamma: f64,
+    visits: VisitType,
+) where
+    S: Ord + Hash + Clone,
+{
+    let returns = discounted_returns(rewards, gamma);
+    let mut seen = HashSet::new();
+    for (state, g) in states.into_iter().zip(returns) {
+        if visits == VisitType::First && !seen.insert(state.clone()) {
+            continue;
+        }
+        let (sum, count) = totals.entry(state).or_insert((0.0, 0));
+        *sum += g;
+        *count += 1;
+    }
+}
+
+fn average_returns<S>(totals: ReturnTotals<S>) -> ValueFunction<S>
+where
+    S: Ord + Hash,
+{
+    totals
+        .into_iter()
+        .map(|(state, (sum, count))| (state, sum / count as f64))
+        .collect()
+}
+
+/// Number of episodes simulated with each random number generator by
+/// [`mc_prediction_parallel`].
+#[cfg(feature = "parallel")]
+const EPISODES_PER_CHUNK: usize = 1024;
+
+/// Returns a Monte Carlo estimate of the value function of `process` from `num_episodes`
+/// episodes simulated in parallel, each starting from a state sampled from
+/// `start_state_dist` and running until it reaches a terminal state.
+///
+/// Returns are averaged as in [`mc_prediction`]. Episodes are simulated in fixed-size
+/// chunks, each with its own generator seeded from `seed` and the chunk index, so the
+/// result depends only on `seed` and not on how the chunks are scheduled across threads.
+#[cfg(feature = "parallel")]
+pub fn mc_prediction_parallel<S, P, D>(
+    process: &P,
+    start_state_dist: &D,
+    gamma: f64,
+    num_episodes: usize,
+    visits: VisitType,
+    seed: u64,
+) -> ValueFunction<S>
+where
+    S: Ord + Hash + Clone + Send,
+    P: MarkovRewardProcess<S> + Sync,
+    D: Distribution<S> + Sync,
+{
+    let num_chunks = num_episodes.div_ceil(EPISODES_PER_CHUNK);
+    let totals = (0..num_chunks)
+        .into_par_iter()
+        .map(|chunk| {
+            let mut rng =
+                StdRng::seed_from_u64(seed ^ (chunk as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+            let episodes = EPISODES_PER_CHUNK.min(num_episodes - chunk * EPISODES_PER_CHUNK);
+
+            let mut totals = HashMap::new();
+            for _ in 0..episodes {
+                let mut states = Vec::new();
+                let mut rewards = Vec::new();
+                let mut state = NonTerminal::new(start_state_dist.sample_with(&mut rng));
+                while let Some(transition) = process.transition_reward(&state) {
+                    let (next_state, reward) = transition.sample_with(&mut rng);
+                    states.push(state);
+                    rewards.push(reward.0);
+                    match next_state {
+                        State::NonTerminal(next_state) => state = next_state,
+                        State::Terminal(_) => break,
+                    }
+                }
+                accumulate_returns(&mut totals, states, &rewards, gamma, visits);
+            }
+            totals
+        })
+        .reduce(HashMap::new, |mut merged, totals| {
+            for (state, (sum, count)) in totals {
+                let (merged_sum, merged_count) = merged.entry(state).or_insert((0.0, 0));
+                *merged_sum += sum;
+                *merged_count += count;
+            }
+            merged
+        });
+    average_returns(totals)
+}
+
+// --------------------------------------------------------------------------------------
+
+// [ Control ] ==========================================================================
+
+/// Returns the action-value function of the greedy target policy learned by off-policy
+/// Monte Carlo control with weighted importance sampling, together with that policy.
+///
+/// Each episode is a sequence of `(state, action, reward, next_state)` transitions, as
+/// yielded by [`crate::markov_decision_process::FiniteMarkovDecisionProcess::simulate`],
+/// generated by following `behavior`. Each episode is processed backwards, accumulating
+/// the return G and the importance weight W, starting from 1. Each pair (s, a) has its
+/// cumulative weight increased by W, C(s, a) ← C(s, a) + W, and is updated as
+/// Q(s, a) ← Q(s, a) + W / C(s, a) (G - Q(s, a)). The target policy is then made greedy
+/// in s among the actions seen there. If a differs from the greedy action, the target
+/// policy would never have taken it, so the earlier steps of the episode have zero weight
+/// and are skipped. Otherwise W ← W / b(a|s).
+///
+/// Panics if `behavior` does not cover a visited state.
+pub fn off_policy_mc_control<S, A, I, E, P>(
+    episodes: I,
+    behavior: &P,
+    gamma: f64,
+) -> (ActionValueFunction<S, A>, DeterministicPolicy<S, A>)
+where
+    S: Ord + Hash + Clone,
+    A: Ord + Hash + Clone,
+    I: IntoIterator<Item = E>,
+    E: IntoIterator<Item = (NonTerminal<S>, A, f64, State<S>)>,
+    P: Policy<S, A>,
+    P::ActionDist: FiniteDistribution<A>,
+{
+    let mut q: ActionValueFunction<S, A> = HashMap::new();
+    let mut weights: HashMap<(NonTerminal<S>, A), f64> = HashMap::new();
+    let mut seen_actions: HashMap<NonTerminal<S>, Vec<A>> = HashMap::new();
+    let mut target: HashMap<NonTerminal<S>, A> = HashMap::new();
+
+    for episode in episodes {
+        let steps: Vec<_> = episode.into_iter().collect();
+        for (state, action, _, _) in &steps {
+            let actions = seen_actions.entry(state.clone()).or_default();
+            if !actions.contains(action) {
+                actions.push(action.clone());
+            }
+        }
+
+        let mut g = 0.0;
+        let mut w = 1.0;
+        for (state, action, reward, _) in steps.into_iter().rev() {
+            g = reward + gamma * g;
+            let key = (state.clone(), action.clone());
+            let c = weights.entry(key.clone()).or_insert(0.0);
+            *c += w;
+            let step_size = w / *c;
+            let value = q.entry(key).or_insert(0.0);
+            *value += step_size * (g - *value);
+
+            let greedy = greedy_action(&q, &state, &seen_actions[&state])
+                .expect("visited state has a seen action")
+                .clone();
+            let diverged = greedy != action;
+            target.insert(state.clone(), greedy);
+            if diverged {
+                break;
+            }
+            w /= behavior
+                .act(&state)
+                .expect("behavior policy covers every visited state")
+                .probability(&action);
+        }
+    }
+
+    let policy = DeterministicPolicy::new(
+        target
+            .into_iter()
+            .map(|(state, action)| (state.state().clone(), action))
+            .collect(),
+    );
+    (q, policy)
+}
+
+// --------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distribution::{Categorical, Distribution};
+    use crate::markov_process::{
+        FiniteMarkovRewardProcess, MarkovRewardProcess, Reward, State, StateReward, Terminal,
+    };
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// A reward process on states 0 and 1 that moves from each state to the other or to
+    /// the terminal state 2 with equal probability, with reward 1 for every transition.
+    /// For discount γ both states have value 1 / (1 - γ / 2).
+    fn chain() -> FiniteMarkovRewardProcess<u32, StateReward<u32>> {
+        let outcomes = |other: u32| {
+            Categorical::new(HashMap::from([
+                (
+                    (State::NonTerminal(NonTerminal::new(other)), Reward(1.0)),
+                    0.5,
+                ),
+                ((State::Terminal(Terminal::new(2)), Reward(1.0)), 0.5),
+            ]))
+            .unwrap()
+        };
+        FiniteMarkovRewardProcess::new(HashMap::from([(0, outcomes(1)), (1, outcomes(0))])).unwrap()
+    }
+
+    /// Returns `num_episodes` episodes of `mrp` starting from state 0.
+    fn episodes(
+        mrp: &FiniteMarkovRewardProcess<u32, StateReward<u32>>,
+        num_episodes: usize,
+        seed: u64,
+    ) -> Vec<Vec<(NonTerminal<u32>, f64)>> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..num_episodes)
+            .map(|_| {
+                let mut episode = Vec::new();
+                let mut state = NonTerminal::new(0);
+                while let Some(transition) = mrp.transition_reward(&state) {
+                    let (next_state, reward) = transition.sample_with(&mut rng);
+                    episode.push((state, reward.0));
+                    match next_state {
+                        State::NonTerminal(next_state) => state = next_state,
+                        State::Terminal(_) => break,
+                    }
+                }
+                episode
+            })
+            .collect()
+    }
+
+    #[test]
+    fn mc_prediction_error_shrinks_as_episodes_grow() {
+        let mrp = chain();
+        let expected = mrp.value_function(0.9).unwrap();
+        let error = |num_episodes: usize| {
+            let vf = mc_prediction(episodes(&mrp, num_episodes, 3), 0.9, VisitType::First);
+            expected
+                .iter()
+                .map(|(state, value)| (vf[state] - value).abs())
+                .fold(0.0, f64::max)
+        };
+        let errors: Vec<f64> = [100, 10_000, 100_000].into_iter().map(error).collect();
+        assert!(errors[0] > errors[1] && errors[1] > errors[2], "{errors:?}");
+        assert!(errors[2] < 0.02, "{errors:?}");
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_and_serial_estimates_agree() {
+        let mrp = chain();
+        let start = crate::distribution::Constant::new(0);
+        let serial = mc_prediction(episodes(&mrp, 20_000, 5), 0.9, VisitType::First);
+        let parallel = mc_prediction_parallel(&mrp, &start, 0.9, 20_000, VisitType::First, 5);
+        assert_eq!(parallel.len(), serial.len());
+        for (state, value) in &serial {
+            assert!((parallel[state] - value).abs() < 0.05);
+        }
+        let again = mc_prediction_parallel(&mrp, &start, 0.9, 20_000, VisitType::First, 5);
+        assert_eq!(again, parallel);
+    }
+
+    #[test]
+    fn first_and_every_visit_differ_on_a_revisited_state() {
+        let (a, b) = (NonTerminal::new(0), NonTerminal::new(1));
+        let episode = vec![(a, 1.0), (b, 1.0), (a, 1.0)];
+        // The returns from the three steps are 3, 2 and 1.
+        let first = mc_prediction([episode.clone()], 1.0, VisitType::First);
+        let every = mc_prediction([episode], 1.0, VisitType::Every);
+        assert_eq!(first[&a], 3.0);
+        assert_eq!(every[&a], 2.0);
+        assert_eq!(first[&b], 2.0);
+        assert_eq!(every[&b], 2.0);
+    }
+
+    #[test]
+    fn off_policy_control_recovers_the_optimal_policy() {
+        use crate::gridworld::{Direction, GridworldBuilder};
+        use crate::policy::StochasticPolicy;
+
+        // A corridor of three cells ending in a goal worth 10, where every move costs 1.
+        let mdp = GridworldBuilder::new(1, 3)
+            .terminal((0, 2), 10.0)
+            .step_cost(1.0)
+            .build()
+            .unwrap();
+        let uniform = Categorical::new(Direction::ALL.map(|d| (d, 1.0)).into()).unwrap();
+        let behavior = StochasticPolicy::new(HashMap::from([
+            ((0, 0), uniform.clone()),
+            ((0, 1), uniform),
+        ]));
+        let start = Categorical::new(HashMap::from([((0, 0), 1.0), ((0, 1), 1.0)])).unwrap();
+        let mut rng = StdRng::seed_from_u64(74);
+        let episodes: Vec<Vec<_>> = (0..2_000)
+            .map(|_| mdp.simulate_with(&start, &behavior, &mut rng).collect())
+            .collect();
+
+        let (q, policy) = off_policy_mc_control(episodes, &behavior, 0.9);
+        for state in mdp.mapping().keys() {
+            assert_eq!(policy.action_for(state), Some(&Direction::Right));
+        }
+        // Along the optimal path the returns are deterministic: 9 and -1 + 0.9 · 9.
+        assert!((q[&(NonTerminal::new((0, 1)), Direction::Right)] - 9.0).abs() < 1e-9);
+        assert!((q[&(NonTerminal::new((0, 0)), Direction::Right)] - 7.1).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,366 @@
+use crate::distribution::{Categorical, Constant, Distribution};
+use crate::error::{Error, Result};
+use crate::markov_decision_process::ActionValueFunction;
+use crate::markov_process::NonTerminal;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+// Trait: `Policy` ======================================================================
+
+/// A rule for choosing actions in the non-terminal states of a decision process.
+pub trait Policy<S, A> {
+    /// The distribution of actions returned by [`Policy::act`].
+    type ActionDist: Distribution<A>;
+
+    /// Returns the distribution of actions to take in `state`, or `None` if the policy
+    /// does not cover `state`.
+    fn act(&self, state: &NonTerminal<S>) -> Option<&Self::ActionDist>;
+}
+
+// --------------------------------------------------------------------------------------
+
+// [ Greedy Actions ] ===================================================================
+
+/// Returns the action with the largest value in `q` among `actions`, treating missing
+/// entries as zero, or `None` if `actions` is empty. Ties go to the action that comes first
+/// in `actions`.
+pub(crate) fn greedy_action<'a, S, A>(
+    q: &ActionValueFunction<S, A>,
+    state: &NonTerminal<S>,
+    actions: &'a [A],
+) -> Option<&'a A>
+where
+    S: Ord + Hash + Clone,
+    A: Ord + Hash + Clone,
+{
+    let value = |action: &A| {
+        q.get(&(state.clone(), action.clone()))
+            .copied()
+            .unwrap_or(0.0)
+    };
+    let (first, rest) = actions.split_first()?;
+    let mut best = first;
+    let mut best_value = value(best);
+    for action in rest {
+        let v = value(action);
+        if v > best_value {
+            best = action;
+            best_value = v;
+        }
+    }
+    Some(best)
+}
+
+// --------------------------------------------------------------------------------------
+
+// Struct: `DeterministicPolicy` ========================================================
+
+/// A policy that always takes the same action in a given state.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeterministicPolicy<S, A>
+where
+    S: Ord + Hash,
+{
+    action_for: HashMap<NonTerminal<S>, Constant<A>>,
+}
+
+impl<S, A> DeterministicPolicy<S, A>
+where
+    S: Ord + Hash,
+{
+    /// Create a policy from a map of each state to the action to take in it.
+    pub fn new(action_for: HashMap<S, A>) -> Self {
+        let action_for = action_for
+            .into_iter()
+            .map(|(state, action)| (NonTerminal::new(state), Constant::new(action)))
+            .collect();
+        DeterministicPolicy { action_for }
+    }
+
+    /// Returns the action this policy takes in `state`.
+    pub fn action_for(&self, state: &NonTerminal<S>) -> Option<&A> {
+        self.action_for.get(state).map(Constant::value)
+    }
+
+    /// Create the policy that is greedy with respect to the action-value function `q`,
+    /// taking in each of `states` the action among `actions(state)` with the largest
+    /// value. Missing entries of `q` count as zero, ties go to the action listed first,
+    /// and states without actions are left out.
+    pub fn greedy_from_q<F>(
+        q: &ActionValueFunction<S, A>,
+        states: &[NonTerminal<S>],
+        actions: F,
+    ) -> Self
+    where
+        S: Clone,
+        A: Ord + Hash + Clone,
+        F: Fn(&NonTerminal<S>) -> Vec<A>,
+    {
+        let mut action_for = HashMap::with_capacity(states.len());
+        for state in states {
+            if let Some(action) = greedy_action(q, state, &actions(state)) {
+                action_for.insert(state.clone(), Constant::new(action.clone()));
+            }
+        }
+        DeterministicPolicy { action_for }
+    }
+}
+
+impl<S, A> Policy<S, A> for DeterministicPolicy<S, A>
+where
+    S: Ord + Hash,
+    A: Clone,
+{
+    type ActionDist = Constant<A>;
+
+    fn act(&self, state: &NonTerminal<S>) -> Option<&Constant<A>> {
+        self.action_for.get(state)
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+// Struct: `StochasticPolicy` ===========================================================
+
+/// A policy that samples the action to take in a given state from a finite distribution.
+#[derive(Clone, Debug)]
+pub struct StochasticPolicy<S, A>
+where
+    S: Ord + Hash,
+    A: Ord + Hash,
+{
+    policy_map: HashMap<NonTerminal<S>, Categorical<A>>,
+}
+
+impl<S, A> StochasticPolicy<S, A>
+where
+    S: Ord + Hash,
+    A: Ord + Hash,
+{
+    /// Create a policy from a map of each state to the distribution of actions in it.
+    pub fn new(policy_map: HashMap<S, Categorical<A>>) -> Self {
+        let policy_map = policy_map
+            .into_iter()
+            .map(|(state, actions)| (NonTerminal::new(state), actions))
+            .collect();
+        StochasticPolicy { policy_map }
+    }
+
+    /// Create the ε-greedy policy with respect to the action-value function `q`: in each
+    /// of `states` it picks uniformly among `actions(state)` with probability `epsilon`,
+    /// and otherwise takes the greedy action chosen as in
+    /// [`DeterministicPolicy::greedy_from_q`]. With `epsilon` = 0 it always takes the
+    /// greedy action. States without actions are left out.
+    ///
+    /// Returns [`Error::InvalidProbability`] unless `epsilon` lies in [0, 1].
+    pub fn epsilon_greedy_from_q<F>(
+        q: &ActionValueFunction<S, A>,
+        states: &[NonTerminal<S>],
+        actions: F,
+        epsilon: f64,
+    ) -> Result<Self>
+    where
+        S: Clone,
+        A: Clone,
+        F: Fn(&NonTerminal<S>) -> Vec<A>,
+    {
+        if !(0.0..=1.0).contains(&epsilon) {
+            return Err(Error::InvalidProbability(epsilon));
+        }
+
+        let mut policy_map = HashMap::with_capacity(states.len());
+        for state in states {
+            let actions = actions(state);
+            let Some(greedy) = greedy_action(q, state, &actions) else {
+                continue;
+            };
+            let explore = epsilon / actions.len() as f64;
+            let mut weights = HashMap::with_capacity(actions.len());
+            for action in &actions {
+                if explore > 0.0 {
+                    weights.insert(action.clone(), explore);
+                }
+            }
+            *weights.entry(greedy.clone()).or_insert(0.0) += 1.0 - epsilon;
+            policy_map.insert(state.clone(), Categorical::new(weights)?);
+        }
+        Ok(StochasticPolicy { policy_map })
+    }
+
+    /// Create the softmax (Boltzmann) policy with respect to the action-value function
+    /// `q`: in each of `states` it picks among `actions(state)` with probability
+    /// π(a | s) ∝ exp(Q(s, a) / T) for the temperature T = `temperature`. Missing entries
+    /// of `q` count as zero, and states without actions are left out.
+    ///
+    /// As T → 0 the policy approaches the greedy policy, splitting ties evenly, and as
+    /// T → ∞ it approaches the uniform policy. The largest value in each state is
+    /// subtracted before exponentiating, so large values cannot overflow.
+    ///
+    /// Returns [`Error::InvalidParameter`] unless `temperature` is positive.
+    pub fn softmax_from_q<F>(
+        q: &ActionValueFunction<S, A>,
+        states: &[NonTerminal<S>],
+        actions: F,
+        temperature: f64,
+    ) -> Result<Self>
+    where
+        S: Clone,
+        A: Clone,
+        F: Fn(&NonTerminal<S>) -> Vec<A>,
+    {
+        if temperature.is_nan() || temperature <= 0.0 {
+            return Err(Error::InvalidParameter {
+                name: "temperature",
+                value: temperature,
+            });
+        }
+
+        let mut policy_map = HashMap::with_capacity(states.len());
+        for state in states {
+            let values: Vec<(A, f64)> = actions(state)
+                .into_iter()
+                .map(|action| {
+                    let value = q
+                        .get(&(state.clone(), action.clone()))
+                        .copied()
+                        .unwrap_or(0.0);
+                    (action, value)
+                })
+                .collect();
+            if values.is_empty() {
+                continue;
+            }
+            let max = values
+                .iter()
+                .map(|&(_, value)| value)
+                .fold(f64::NEG_INFINITY, f64::max);
+            let mut weights = HashMap::with_capacity(values.len());
+            for (action, value) in values {
+                let weight = ((value - max) / temperature).exp();
+                if weight > 0.0 {
+                    *weights.entry(action).or_insert(0.0) += weight;
+                }
+            }
+            policy_map.insert(state.clone(), Categorical::new(weights)?);
+        }
+        Ok(StochasticPolicy { policy_map })
+    }
+}
+
+impl<S, A> Policy<S, A> for StochasticPolicy<S, A>
+where
+    S: Ord + Hash,
+    A: Ord + Hash + Clone,
+{
+    type ActionDist = Categorical<A>;
+
+    fn act(&self, state: &NonTerminal<S>) -> Option<&Categorical<A>> {
+        self.policy_map.get(state)
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distribution::FiniteDistribution;
+
+    #[test]
+    fn deterministic_policy_takes_mapped_action_with_certainty() {
+        let policy = DeterministicPolicy::new(HashMap::from([(0, 'a'), (1, 'b')]));
+        let actions = policy.act(&NonTerminal::new(1)).unwrap();
+        assert_eq!(actions.probability(&'b'), 1.0);
+        assert_eq!(actions.probability(&'a'), 0.0);
+        assert_eq!(policy.action_for(&NonTerminal::new(0)), Some(&'a'));
+        assert!(policy.act(&NonTerminal::new(2)).is_none());
+    }
+
+    #[test]
+    fn stochastic_policy_returns_mapped_distribution() {
+        let actions = Categorical::new(HashMap::from([('a', 0.25), ('b', 0.75)])).unwrap();
+        let policy = StochasticPolicy::new(HashMap::from([(0, actions.clone())]));
+        assert_eq!(policy.act(&NonTerminal::new(0)), Some(&actions));
+        assert!(policy.act(&NonTerminal::new(1)).is_none());
+    }
+
+    #[test]
+    fn greedy_action_of_no_actions_is_none() {
+        let q: ActionValueFunction<u32, char> = HashMap::new();
+        assert_eq!(greedy_action(&q, &NonTerminal::new(0), &[]), None);
+    }
+
+    #[test]
+    fn greedy_action_takes_largest_value() {
+        let state = NonTerminal::new(0);
+        let q = HashMap::from([((state, 'a'), -1.0), ((state, 'c'), 2.0)]);
+        assert_eq!(greedy_action(&q, &state, &['a', 'b', 'c']), Some(&'c'));
+    }
+
+    #[test]
+    fn greedy_action_breaks_ties_towards_the_first_action() {
+        let state = NonTerminal::new(0);
+        let q = HashMap::from([((state, 'a'), 1.0), ((state, 'b'), 1.0)]);
+        assert_eq!(greedy_action(&q, &state, &['b', 'a']), Some(&'b'));
+    }
+
+    #[test]
+    fn epsilon_greedy_without_exploration_is_greedy() {
+        let states = [NonTerminal::new(0), NonTerminal::new(1)];
+        let q = HashMap::from([
+            ((states[0], 'a'), 1.0),
+            ((states[0], 'b'), 3.0),
+            ((states[1], 'a'), 2.0),
+            ((states[1], 'b'), 2.0),
+        ]);
+        let actions = |_: &NonTerminal<u32>| vec!['a', 'b'];
+        let greedy = DeterministicPolicy::greedy_from_q(&q, &states, actions);
+        let epsilon_greedy =
+            StochasticPolicy::epsilon_greedy_from_q(&q, &states, actions, 0.0).unwrap();
+        for state in &states {
+            let action = greedy.action_for(state).unwrap();
+            let dist = epsilon_greedy.act(state).unwrap();
+            assert_eq!(dist.probability(action), 1.0);
+            assert_eq!(dist.table().len(), 1);
+        }
+        assert_eq!(greedy.action_for(&states[1]), Some(&'a'));
+
+        let exploring = StochasticPolicy::epsilon_greedy_from_q(&q, &states, actions, 0.5).unwrap();
+        let dist = exploring.act(&states[0]).unwrap();
+        assert_eq!(dist.probability(&'b'), 0.75);
+        assert_eq!(dist.probability(&'a'), 0.25);
+        assert!(StochasticPolicy::epsilon_greedy_from_q(&q, &states, actions, 1.5).is_err());
+    }
+
+    #[test]
+    fn higher_temperature_flattens_the_softmax_policy() {
+        let states = [NonTerminal::new(0)];
+        let q = HashMap::from([
+            ((states[0], 'a'), 1.0),
+            ((states[0], 'b'), 2.0),
+            ((states[0], 'c'), 4.0),
+        ]);
+        let actions = |_: &NonTerminal<u32>| vec!['a', 'b', 'c'];
+        let best_probability = |temperature| {
+            let policy =
+                StochasticPolicy::softmax_from_q(&q, &states, actions, temperature).unwrap();
+            policy.act(&states[0]).unwrap().probability(&'c')
+        };
+        let probabilities: Vec<f64> = [0.01, 0.5, 1.0, 10.0, 1_000.0]
+            .into_iter()
+            .map(best_probability)
+            .collect();
+        assert!(
+            probabilities.windows(2).all(|pair| pair[0] > pair[1]),
+            "{probabilities:?}"
+        );
+        assert!(probabilities[0] > 1.0 - 1e-9);
+        assert!((probabilities[4] - 1.0 / 3.0).abs() < 1e-2);
+
+        // Large values do not overflow.
+        let q = HashMap::from([((states[0], 'a'), 1e4), ((states[0], 'b'), 1e4)]);
+        let policy = StochasticPolicy::softmax_from_q(&q, &states, actions, 1.0).unwrap();
+        assert!((policy.act(&states[0]).unwrap().probability(&'a') - 0.5).abs() < 1e-12);
+        assert!(StochasticPolicy::softmax_from_q(&q, &states, actions, 0.0).is_err());
+    }
+}
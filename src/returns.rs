@@ -0,0 +1,137 @@
+use crate::markov_process::{NonTerminal, State, ValueFunction};
+use std::hash::Hash;
+
+// [ Discounted Returns ] ===============================================================
+
+/// Returns the discounted return Σ γᵗ rₜ of a sequence of rewards, where `rewards[0]` is
+/// received first.
+pub fn discounted_return(rewards: &[f64], gamma: f64) -> f64 {
+    rewards
+        .iter()
+        .rev()
+        .fold(0.0, |future, reward| reward + gamma * future)
+}
+
+/// Returns the discounted return Gₜ = rₜ + γGₜ₊₁ from each time step of a sequence of
+/// rewards onward, computed back-to-front in a single pass. The first element equals
+/// [`discounted_return`] of the whole sequence.
+pub fn discounted_returns(rewards: &[f64], gamma: f64) -> Vec<f64> {
+    let mut returns = vec![0.0; rewards.len()];
+    let mut future = 0.0;
+    for (t, reward) in rewards.iter().enumerate().rev() {
+        future = reward + gamma * future;
+        returns[t] = future;
+    }
+    returns
+}
+
+/// Returns the discounted reward-to-go from each transition of an episode of
+/// `(state, reward, next_state)` transitions onward, as in [`discounted_returns`].
+pub fn rewards_to_go<S>(episode: &[(NonTerminal<S>, f64, State<S>)], gamma: f64) -> Vec<f64> {
+    let rewards: Vec<f64> = episode.iter().map(|(_, reward, _)| *reward).collect();
+    discounted_returns(&rewards, gamma)
+}
+
+// --------------------------------------------------------------------------------------
+
+// [ Advantages ] =======================================================================
+
+/// Returns the advantage estimate of each transition of an episode of
+/// `(state, reward, next_state)` transitions as its TD error A = r + γV(s') - V(s) under
+/// the value function `vf`, where the value of terminal states is zero and of states
+/// missing from `vf` is zero.
+pub fn advantages<S>(
+    episode: &[(NonTerminal<S>, f64, State<S>)],
+    vf: &ValueFunction<S>,
+    gamma: f64,
+) -> Vec<f64>
+where
+    S: Eq + Hash,
+{
+    let value = |state: &NonTerminal<S>| vf.get(state).copied().unwrap_or(0.0);
+    episode
+        .iter()
+        .map(|(state, reward, next_state)| {
+            let next_value = match next_state {
+                State::NonTerminal(next_state) => value(next_state),
+                State::Terminal(_) => 0.0,
+            };
+            reward + gamma * next_value - value(state)
+        })
+        .collect()
+}
+
+// --------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markov_process::Terminal;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::collections::HashMap;
+
+    #[test]
+    fn discounted_returns_of_a_geometric_example() {
+        let rewards = [1.0, 2.0, 4.0];
+        // G₂ = 4, G₁ = 2 + 0.5 · 4 = 4, G₀ = 1 + 0.5 · 4 = 3.
+        assert_eq!(discounted_returns(&rewards, 0.5), vec![3.0, 4.0, 4.0]);
+        assert_eq!(discounted_return(&rewards, 0.5), 3.0);
+    }
+
+    #[test]
+    fn discounted_returns_at_the_extremes_of_gamma() {
+        let rewards = [1.0, -2.0, 3.0, 0.5];
+        assert_eq!(discounted_returns(&rewards, 0.0), rewards.to_vec());
+        assert_eq!(discounted_returns(&rewards, 1.0), vec![2.5, 1.5, 3.5, 0.5]);
+        assert!(discounted_returns(&[], 0.9).is_empty());
+        assert_eq!(discounted_return(&[], 0.9), 0.0);
+    }
+
+    #[test]
+    fn advantages_bootstrap_zero_from_terminal_states() {
+        let episode = vec![
+            (
+                NonTerminal::new(0),
+                1.0,
+                State::NonTerminal(NonTerminal::new(1)),
+            ),
+            (NonTerminal::new(1), 2.0, State::Terminal(Terminal::new(2))),
+        ];
+        let vf = HashMap::from([(NonTerminal::new(0), 4.0), (NonTerminal::new(1), 3.0)]);
+        // 1 + 0.5 · 3 - 4 and 2 + 0 - 3.
+        assert_eq!(advantages(&episode, &vf, 0.5), vec![-1.5, -1.0]);
+        assert_eq!(rewards_to_go(&episode, 0.5), vec![2.0, 2.0]);
+    }
+
+    #[test]
+    fn advantages_under_the_true_value_function_average_to_zero() {
+        // A random walk on 1 to 5 that ends at 0 or 6, with reward 1 for reaching 6, has
+        // undiscounted values s / 6. Its episodes are joined into one long one.
+        let vf: ValueFunction<u32> = (1..=5)
+            .map(|s| (NonTerminal::new(s), s as f64 / 6.0))
+            .collect();
+        let mut rng = StdRng::seed_from_u64(70);
+        let mut episode = Vec::new();
+        let mut state = 3;
+        while episode.len() < 100_000 {
+            let next_state = if rng.gen_bool(0.5) {
+                state + 1
+            } else {
+                state - 1
+            };
+            let reward = if next_state == 6 { 1.0 } else { 0.0 };
+            if next_state == 0 || next_state == 6 {
+                let next = State::Terminal(Terminal::new(next_state));
+                episode.push((NonTerminal::new(state), reward, next));
+                state = 3;
+            } else {
+                let next = State::NonTerminal(NonTerminal::new(next_state));
+                episode.push((NonTerminal::new(state), reward, next));
+                state = next_state;
+            }
+        }
+        let mean = advantages(&episode, &vf, 1.0).iter().sum::<f64>() / episode.len() as f64;
+        assert!(mean.abs() < 0.01, "mean = {mean}");
+    }
+}
@@ -0,0 +1,298 @@
+use crate::error::{Error, Result};
+use std::ops::RangeInclusive;
+
+// Struct: `Histogram` ==================================================================
+
+/// Counts of real-valued samples in equal-width bins over a range, such as the samples of
+/// a continuous [`crate::distribution::Distribution`].
+///
+/// Samples outside of the range are counted in the nearest edge bin, and also in
+/// [`Histogram::out_of_range`]. `NaN` samples are ignored.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+    range: RangeInclusive<f64>,
+    counts: Vec<usize>,
+    out_of_range: usize,
+}
+
+impl Histogram {
+    /// Create a histogram of `samples` in `num_bins` bins of equal width over `range`.
+    /// Each bin includes its lower edge, and the last bin also includes the end of the
+    /// range.
+    ///
+    /// Returns [`Error::InvalidParameter`] if `num_bins` is zero, or unless the bounds of
+    /// `range` are finite and its start is below its end.
+    pub fn from_samples<I>(samples: I, num_bins: usize, range: RangeInclusive<f64>) -> Result<Self>
+    where
+        I: IntoIterator<Item = f64>,
+    {
+        if num_bins == 0 {
+            return Err(Error::InvalidParameter {
+                name: "num_bins",
+                value: 0.0,
+            });
+        }
+        let (&start, &end) = (range.start(), range.end());
+        if !start.is_finite() {
+            return Err(Error::InvalidParameter {
+                name: "range",
+                value: start,
+            });
+        }
+        if !end.is_finite() || end <= start {
+            return Err(Error::InvalidParameter {
+                name: "range",
+                value: end,
+            });
+        }
+
+        let mut histogram = Histogram {
+            range,
+            counts: vec![0; num_bins],
+            out_of_range: 0,
+        };
+        for x in samples {
+            histogram.add(x);
+        }
+        Ok(histogram)
+    }
+
+    /// Adds the sample `x` to this histogram.
+    pub fn add(&mut self, x: f64) {
+        if x.is_nan() {
+            return;
+        }
+        if !self.range.contains(&x) {
+            self.out_of_range += 1;
+        }
+        let position = (x - self.range.start()) / self.bin_width();
+        let last = self.counts.len() - 1;
+        let bin = if position < 0.0 {
+            0
+        } else {
+            (position as usize).min(last)
+        };
+        self.counts[bin] += 1;
+    }
+
+    /// Returns the width of each bin.
+    pub fn bin_width(&self) -> f64 {
+        (self.range.end() - self.range.start()) / self.counts.len() as f64
+    }
+
+    /// Returns the lower and upper edges of bin `i`.
+    ///
+    /// Panics if `i` is not the index of a bin.
+    pub fn bin_edges(&self, i: usize) -> (f64, f64) {
+        assert!(i < self.counts.len(), "bin {} is out of range", i);
+        let width = self.bin_width();
+        let low = self.range.start() + i as f64 * width;
+        (low, low + width)
+    }
+
+    /// Returns the number of samples in each bin.
+    pub fn counts(&self) -> &[usize] {
+        &self.counts
+    }
+
+    /// Returns the number of samples counted, including those outside of the range.
+    pub fn total(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    /// Returns the number of samples outside of the range, which are counted in the edge
+    /// bins.
+    pub fn out_of_range(&self) -> usize {
+        self.out_of_range
+    }
+
+    /// Returns the density estimate in each bin, its count divided by the total count and
+    /// the bin width, so that the densities integrate to 1 over the range. The densities
+    /// are `NaN` if the histogram is empty.
+    pub fn densities(&self) -> Vec<f64> {
+        let scale = self.total() as f64 * self.bin_width();
+        self.counts.iter().map(|&c| c as f64 / scale).collect()
+    }
+
+    /// Returns the empirical cumulative distribution function at the upper edge of each
+    /// bin: the fraction of samples in that bin or below it. The fractions are `NaN` if
+    /// the histogram is empty.
+    pub fn cdf(&self) -> Vec<f64> {
+        let total = self.total() as f64;
+        let mut cumulative = 0;
+        self.counts
+            .iter()
+            .map(|&c| {
+                cumulative += c;
+                cumulative as f64 / total
+            })
+            .collect()
+    }
+}
+
+// Struct: `LengthStats` ================================================================
+
+/// Summary statistics of the lengths of a batch of traces, as returned by
+/// [`trace_length_stats`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LengthStats {
+    /// The mean length.
+    pub mean: f64,
+    /// The standard deviation of the lengths.
+    pub std: f64,
+    /// The shortest length.
+    pub min: usize,
+    /// The longest length.
+    pub max: usize,
+    /// The number of traces cut off at the maximum length.
+    pub capped: usize,
+}
+
+/// Returns statistics of the lengths of the first `num_traces` traces of `traces`, such as
+/// the iterator of [`crate::markov_process::MarkovProcess::traces_iter`], where the length
+/// of a trace is the number of items it yields. For traces of states from
+/// [`crate::markov_process::MarkovProcess::simulate_iter`], that is one more than the
+/// number of steps taken, since both the start state and the terminal state are yielded.
+///
+/// Traces are cut off after `max_length` items, so that a process which rarely or never
+/// terminates cannot hang the computation; they count as having length `max_length`, and
+/// are counted in [`LengthStats::capped`]. Many capped traces suggest a mis-specified
+/// process.
+///
+/// Returns [`Error::Empty`] if `traces` yields no traces or `num_traces` is zero.
+pub fn trace_length_stats<I>(traces: I, num_traces: usize, max_length: usize) -> Result<LengthStats>
+where
+    I: IntoIterator,
+    I::Item: IntoIterator,
+{
+    let mut count = 0;
+    let mut mean = 0.0;
+    let mut m2 = 0.0;
+    let mut min = usize::MAX;
+    let mut max = 0;
+    let mut capped = 0;
+    for trace in traces.into_iter().take(num_traces) {
+        let mut trace = trace.into_iter();
+        let length = trace.by_ref().take(max_length).count();
+        if length == max_length && trace.next().is_some() {
+            capped += 1;
+        }
+        count += 1;
+        let delta = length as f64 - mean;
+        mean += delta / count as f64;
+        m2 += delta * (length as f64 - mean);
+        min = min.min(length);
+        max = max.max(length);
+    }
+    if count == 0 {
+        return Err(Error::Empty);
+    }
+
+    Ok(LengthStats {
+        mean,
+        std: (m2 / count as f64).sqrt(),
+        min,
+        max,
+        capped,
+    })
+}
+
+// --------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distribution::{Categorical, Constant, Distribution, Uniform};
+    use crate::markov_process::{FiniteMarkovProcess, MarkovProcess};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn uniform_samples_fill_bins_evenly() {
+        let uniform = Uniform::new(0.0, 1.0).unwrap();
+        let samples = uniform.sample_n_with(40_000, &mut StdRng::seed_from_u64(91));
+        let histogram = Histogram::from_samples(samples, 8, 0.0..=1.0).unwrap();
+        assert_eq!(histogram.total(), 40_000);
+        assert_eq!(histogram.out_of_range(), 0);
+        for &count in histogram.counts() {
+            assert!(
+                (count as f64 - 5_000.0).abs() < 300.0,
+                "{:?}",
+                histogram.counts()
+            );
+        }
+        for density in histogram.densities() {
+            assert!((density - 1.0).abs() < 0.06);
+        }
+        let cdf = histogram.cdf();
+        assert!((cdf[3] - 0.5).abs() < 0.02);
+        assert_eq!(cdf[7], 1.0);
+    }
+
+    #[test]
+    fn out_of_range_samples_go_to_the_edge_bins() {
+        let samples = [-3.0, 0.5, 1.5, 2.0, 9.0, f64::NAN];
+        let histogram = Histogram::from_samples(samples, 2, 0.0..=2.0).unwrap();
+        assert_eq!(histogram.counts(), &[2, 3]);
+        assert_eq!(histogram.out_of_range(), 2);
+        assert_eq!(histogram.bin_edges(1), (1.0, 2.0));
+        assert!(Histogram::from_samples([], 0, 0.0..=1.0).is_err());
+        assert!(matches!(
+            Histogram::from_samples([], 2, 1.0..=1.0),
+            Err(Error::InvalidParameter { name: "range", .. })
+        ));
+    }
+
+    #[test]
+    fn empty_histogram_has_nan_densities() {
+        let histogram = Histogram::from_samples([], 2, 0.0..=1.0).unwrap();
+        assert_eq!(histogram.total(), 0);
+        assert!(histogram.densities().iter().all(|d| d.is_nan()));
+        assert!(histogram.cdf().iter().all(|p| p.is_nan()));
+    }
+
+    #[test]
+    fn geometric_trace_lengths_match_the_analytic_mean() {
+        // State 0 ends with probability 1/4 at each step, so it takes 4 steps on average,
+        // and the traces yield one more state than that.
+        let process = FiniteMarkovProcess::new(
+            [(0, Categorical::new([(0, 0.75), (1, 0.25)].into()).unwrap())].into(),
+        );
+        let traces = process.traces_iter_with(Constant::new(0), StdRng::seed_from_u64(100));
+        let stats = trace_length_stats(traces, 20_000, 1_000).unwrap();
+        assert!((stats.mean - 5.0).abs() < 0.1, "mean {}", stats.mean);
+        // A geometric number of steps with success probability 1/4 has variance 12.
+        assert!((stats.std - 12f64.sqrt()).abs() < 0.1, "std {}", stats.std);
+        assert_eq!(stats.min, 2);
+        assert_eq!(stats.capped, 0);
+
+        let looping = FiniteMarkovProcess::new([(0, Constant::new(0))].into());
+        let traces = looping.traces_iter_with(Constant::new(0), StdRng::seed_from_u64(100));
+        let stats = trace_length_stats(traces, 10, 50).unwrap();
+        assert_eq!(stats.capped, 10);
+        assert_eq!(
+            (stats.min, stats.max, stats.mean, stats.std),
+            (50, 50, 50.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn trace_length_stats_caps_only_longer_traces() {
+        let stats = trace_length_stats(vec![vec![1, 2, 3]], 1, usize::MAX).unwrap();
+        assert_eq!((stats.max, stats.capped), (3, 0));
+        let stats = trace_length_stats(vec![vec![1, 2, 3]], 1, 3).unwrap();
+        assert_eq!((stats.max, stats.capped), (3, 0));
+        let stats = trace_length_stats(vec![vec![1, 2, 3]], 1, 2).unwrap();
+        assert_eq!((stats.max, stats.capped), (2, 1));
+
+        let none: Vec<Vec<u8>> = vec![];
+        assert!(matches!(
+            trace_length_stats(none, 10, 50),
+            Err(Error::Empty)
+        ));
+        assert!(matches!(
+            trace_length_stats(vec![vec![1, 2]], 0, 50),
+            Err(Error::Empty)
+        ));
+    }
+}
@@ -0,0 +1,1032 @@
+use crate::distribution::FiniteDistribution;
+use crate::function_approx::{Tabular, ValueFunctionApprox};
+use crate::markov_decision_process::{
+    ActionValueFunction, FiniteMarkovDecisionProcess, MarkovDecisionProcess,
+};
+use crate::markov_process::{NonTerminal, Reward, State, ValueFunction};
+use crate::policy::greedy_action;
+use rand::Rng;
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+
+/// Upper bound on the number of steps in each episode generated by the control
+/// algorithms, so that policies which never reach a terminal state cannot hang them.
+pub const MAX_EPISODE_STEPS: usize = 10_000;
+
+// Enum: `LearningRate` =================================================================
+
+/// The step size α used by incremental updates V(s) ← V(s) + α (target - V(s)), as a
+/// schedule over the number of updates made to each state, or to each state-action pair
+/// for control.
+///
+/// Tabular estimates converge when the step sizes αₙ satisfy the Robbins–Monro conditions
+/// Σ αₙ = ∞ and Σ αₙ² < ∞. A constant step size violates the second, which lets the
+/// estimates keep tracking a changing target instead.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LearningRate {
+    /// The same step size for every update.
+    Constant(f64),
+    /// A step size of 1/n for the n-th update of a state, which makes each estimate the
+    /// running average of its targets.
+    CountBased,
+    /// A step size of `base` / nᵉ for the n-th update of a state, with e = `exponent`. The
+    /// Robbins–Monro conditions hold for exponents in (1/2, 1].
+    PolynomialDecay { base: f64, exponent: f64 },
+}
+
+impl LearningRate {
+    /// Returns the step size for the update to a state that has been updated
+    /// `visit_count` times, counting this update.
+    pub fn rate(&self, visit_count: usize) -> f64 {
+        let n = visit_count.max(1) as f64;
+        match *self {
+            LearningRate::Constant(alpha) => alpha,
+            LearningRate::CountBased => 1.0 / n,
+            LearningRate::PolynomialDecay { base, exponent } => base / n.powf(exponent),
+        }
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+// Struct: `ExperienceReplay` ===========================================================
+
+/// A buffer of the most recent `(state, action, reward, next_state)` transitions observed
+/// in a decision process, holding at most a fixed number of them.
+#[derive(Clone, Debug)]
+pub struct ExperienceReplay<S, A> {
+    capacity: usize,
+    transitions: VecDeque<(NonTerminal<S>, A, f64, State<S>)>,
+}
+
+impl<S, A> ExperienceReplay<S, A> {
+    /// Create an empty buffer that holds at most `capacity` transitions.
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "capacity must be at least 1");
+        ExperienceReplay {
+            capacity,
+            transitions: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Returns the maximum number of transitions held by this buffer.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of transitions in this buffer.
+    pub fn len(&self) -> usize {
+        self.transitions.len()
+    }
+
+    /// Returns `true` if this buffer holds no transitions.
+    pub fn is_empty(&self) -> bool {
+        self.transitions.is_empty()
+    }
+
+    /// Adds `transition` to this buffer, evicting the oldest transition if it is full.
+    pub fn push(&mut self, transition: (NonTerminal<S>, A, f64, State<S>)) {
+        if self.transitions.len() == self.capacity {
+            self.transitions.pop_front();
+        }
+        self.transitions.push_back(transition);
+    }
+
+    /// Returns an iterator over the transitions in this buffer, from oldest to newest.
+    pub fn iter(&self) -> impl Iterator<Item = &(NonTerminal<S>, A, f64, State<S>)> {
+        self.transitions.iter()
+    }
+
+    /// Returns `n` transitions drawn uniformly at random with replacement from this
+    /// buffer, or none if it is empty.
+    pub fn sample_batch<R>(&self, n: usize, rng: &mut R) -> Vec<&(NonTerminal<S>, A, f64, State<S>)>
+    where
+        R: Rng + ?Sized,
+    {
+        if self.transitions.is_empty() {
+            return Vec::new();
+        }
+        (0..n)
+            .map(|_| &self.transitions[rng.gen_range(0..self.transitions.len())])
+            .collect()
+    }
+}
+
+// --------------------------------------------------------------------------------------
+
+// [ Prediction ] =======================================================================
+
+/// Returns the TD(0) estimate of the value function of a reward process from sampled
+/// episodes.
+///
+/// Each episode is a sequence of `(state, reward, next_state)` transitions. For each
+/// transition in turn the estimate is updated as V(s) ← V(s) + α (r + γV(s') - V(s)),
+/// where the value of terminal states is zero and of unseen states starts at zero.
+///
+/// This is [`td_zero_approx`] with a [`Tabular`] value function.
+pub fn td_zero<S, I, E>(episodes: I, gamma: f64, alpha: LearningRate) -> ValueFunction<S>
+where
+    S: Ord + Hash + Clone,
+    I: IntoIterator<Item = E>,
+    E: IntoIterator<Item = (NonTerminal<S>, f64, State<S>)>,
+{
+    td_zero_approx(episodes, gamma, alpha, Tabular::new()).into_values()
+}
+
+/// Improves the approximate value function `vf` of a reward process by TD(0) on sampled
+/// episodes, and returns it.
+///
+/// Episodes are given as for [`td_zero`]. For each transition in turn `vf` is moved
+/// towards the target r + γV(s'), where the value of terminal states is zero.
+pub fn td_zero_approx<S, I, E, V>(episodes: I, gamma: f64, alpha: LearningRate, mut vf: V) -> V
+where
+    S: Ord + Hash + Clone,
+    I: IntoIterator<Item = E>,
+    E: IntoIterator<Item = (NonTerminal<S>, f64, State<S>)>,
+    V: ValueFunctionApprox<NonTerminal<S>>,
+{
+    let mut counts: HashMap<NonTerminal<S>, usize> = HashMap::new();
+    for episode in episodes {
+        for (state, reward, next_state) in episode {
+            let next_value = match &next_state {
+                State::NonTerminal(next_state) => vf.evaluate(next_state),
+                State::Terminal(_) => 0.0,
+            };
+            let count = counts.entry(state.clone()).or_insert(0);
+            *count += 1;
+            let step_size = alpha.rate(*count);
+
+            vf.update(&state, reward + gamma * next_value, step_size);
+        }
+    }
+    vf
+}
+
+/// Returns the TD(λ) estimate of the value function of a reward process from sampled
+/// episodes, using accumulating eligibility traces.
+///
+/// Episodes are given as for [`td_zero`]. At each transition the trace of s is
+/// incremented, every state with a non-zero trace e(x) is updated as
+/// V(x) ← V(x) + α e(x) δ with the TD error δ = r + γV(s') - V(s), and then all traces
+/// decay by γλ. Traces are reset at the start of each episode. With `lambda` = 0 this is
+/// [`td_zero`], and with `lambda` = 1 it approximates every-visit Monte Carlo.
+pub fn td_lambda<S, I, E>(
+    episodes: I,
+    gamma: f64,
+    lambda: f64,
+    alpha: LearningRate,
+) -> ValueFunction<S>
+where
+    S: Ord + Hash + Clone,
+    I: IntoIterator<Item = E>,
+    E: IntoIterator<Item = (NonTerminal<S>, f64, State<S>)>,
+{
+    let mut vf: ValueFunction<S> = HashMap::new();
+    let mut counts: HashMap<NonTerminal<S>, usize> = HashMap::new();
+    for episode in episodes {
+        let mut traces: HashMap<NonTerminal<S>, f64> = HashMap::new();
+        for (state, reward, next_state) in episode {
+            let next_value = match &next_state {
+                State::NonTerminal(next_state) => vf.get(next_state).copied().unwrap_or(0.0),
+                State::Terminal(_) => 0.0,
+            };
+            let td_error = reward + gamma * next_value - vf.get(&state).copied().unwrap_or(0.0);
+            *counts.entry(state.clone()).or_insert(0) += 1;
+            *traces.entry(state).or_insert(0.0) += 1.0;
+
+            for (x, trace) in traces.iter_mut() {
+                let step_size = alpha.rate(counts[x]);
+                *vf.entry(x.clone()).or_insert(0.0) += step_size * *trace * td_error;
+                *trace *= gamma * lambda;
+            }
+            traces.retain(|_, trace| *trace != 0.0);
+        }
+    }
+    vf
+}
+
+/// Returns the n-step TD estimate of the value function of a reward process from sampled
+/// episodes.
+///
+/// Episodes are given as for [`td_zero`]. Each state s_t is updated once n further
+/// transitions have been observed, towards the n-step return
+/// G = r₁ + γr₂ + … + γⁿ⁻¹rₙ + γⁿV(s_{t+n}). Near the end of an episode fewer than n
+/// rewards remain, and the return is cut short at the last successor, whose value is zero
+/// if it is terminal. With `n` = 1 this is [`td_zero`].
+///
+/// Panics if `n` is zero.
+pub fn n_step_td<S, I, E>(
+    episodes: I,
+    gamma: f64,
+    alpha: LearningRate,
+    n: usize,
+) -> ValueFunction<S>
+where
+    S: Ord + Hash + Clone,
+    I: IntoIterator<Item = E>,
+    E: IntoIterator<Item = (NonTerminal<S>, f64, State<S>)>,
+{
+    assert!(n > 0, "n must be at least 1");
+
+    // Updates the oldest state in `window` towards its return bootstrapped from `last`.
+    let update = |vf: &mut ValueFunction<S>,
+                  counts: &mut HashMap<NonTerminal<S>, usize>,
+                  window: &mut VecDeque<(NonTerminal<S>, f64)>,
+                  last: &State<S>| {
+        let mut target = 0.0;
+        let mut discount = 1.0;
+        for (_, reward) in window.iter() {
+            target += discount * reward;
+            discount *= gamma;
+        }
+        if let State::NonTerminal(last) = last {
+            target += discount * vf.get(last).copied().unwrap_or(0.0);
+        }
+
+        let (state, _) = window.pop_front().expect("window is not empty");
+        let count = counts.entry(state.clone()).or_insert(0);
+        *count += 1;
+        let step_size = alpha.rate(*count);
+
+        let value = vf.entry(state).or_insert(0.0);
+        *value += step_size * (target - *value);
+    };
+
+    let mut vf: ValueFunction<S> = HashMap::new();
+    let mut counts: HashMap<NonTerminal<S>, usize> = HashMap::new();
+    for episode in episodes {
+        let mut window = VecDeque::with_capacity(n);
+        let mut last = None;
+        for (state, reward, next_state) in episode {
+            window.push_back((state, reward));
+            if window.len() == n {
+                update(&mut vf, &mut counts, &mut window, &next_state);
+            }
+            last = Some(next_state);
+        }
+        if let Some(last) = last {
+            while !window.is_empty() {
+                update(&mut vf, &mut counts, &mut window, &last);
+            }
+        }
+    }
+    vf
+}
+
+// --------------------------------------------------------------------------------------
+
+// [ Control ] ==========================================================================
+
+/// Picks a uniformly random action from `actions` with probability `epsilon`, and the
+/// greedy action with respect to `q` otherwise. Returns `None` if `actions` is empty.
+fn epsilon_greedy_action<S, A, R>(
+    q: &ActionValueFunction<S, A>,
+    state: &NonTerminal<S>,
+    actions: &[A],
+    epsilon: f64,
+    rng: &mut R,
+) -> Option<A>
+where
+    S: Ord + Hash + Clone,
+    A: Ord + Hash + Clone,
+    R: Rng + ?Sized,
+{
+    if actions.is_empty() {
+        None
+    } else if rng.gen::<f64>() < epsilon {
+        Some(actions[rng.gen_range(0..actions.len())].clone())
+    } else {
+        greedy_action(q, state, actions).cloned()
+    }
+}
+
+/// Increments the number of updates made to the pair (`state`, `action`), and returns it.
+fn increment<S, A>(
+    counts: &mut HashMap<(NonTerminal<S>, A), usize>,
+    state: &NonTerminal<S>,
+    action: &A,
+) -> usize
+where
+    S: Ord + Hash + Clone,
+    A: Ord + Hash + Clone,
+{
+    let count = counts.entry((state.clone(), action.clone())).or_insert(0);
+    *count += 1;
+    *count
+}
+
+/// Runs `num_episodes` episodes of TD control on `mdp` with an ε-greedy behavior policy,
+/// bootstrapping each update from `next_value(q, s', actions, a', ε)`, the estimated value
+/// of the non-terminal successor s' given its actions, the next action a' chosen by the
+/// behavior policy and the current exploration rate ε of that policy. A successor without actions is treated as terminal. Random choices are
+/// drawn using `rng`.
+fn td_control<S, A, X, F, R>(
+    mdp: &FiniteMarkovDecisionProcess<S, A, X>,
+    gamma: f64,
+    alpha: LearningRate,
+    epsilon: f64,
+    num_episodes: usize,
+    next_value: F,
+    rng: &mut R,
+) -> ActionValueFunction<S, A>
+where
+    S: Ord + Hash + Clone,
+    A: Ord + Hash + Clone,
+    X: FiniteDistribution<(State<S>, Reward)>,
+    F: Fn(&ActionValueFunction<S, A>, &NonTerminal<S>, &[A], &A, f64) -> f64,
+    R: Rng + ?Sized,
+{
+    let states = mdp.non_terminal_states();
+    let mut q: ActionValueFunction<S, A> = HashMap::new();
+    let mut counts: HashMap<(NonTerminal<S>, A), usize> = HashMap::new();
+    if states.is_empty() {
+        return q;
+    }
+
+    for k in 1..=num_episodes {
+        let epsilon = epsilon / k as f64;
+        let mut state = states[rng.gen_range(0..states.len())].clone();
+        let mut action = epsilon_greedy_action(&q, &state, &mdp.actions(&state), epsilon, rng)
+            .expect("non-terminal state has actions");
+
+        for _ in 0..MAX_EPISODE_STEPS {
+            let step = mdp
+                .step(&state, &action)
+                .expect("action is available in state");
+            let (next_state, reward) = step.sample_with(rng);
+
+            let (target, next) = match next_state {
+                State::NonTerminal(next_state) => {
+                    let actions = mdp.actions(&next_state);
+                    match epsilon_greedy_action(&q, &next_state, &actions, epsilon, rng) {
+                        Some(next_action) => {
+                            let target = reward.0
+                                + gamma
+                                    * next_value(&q, &next_state, &actions, &next_action, epsilon);
+                            (target, Some((next_state, next_action)))
+                        }
+                        None => (reward.0, None),
+                    }
+                }
+                State::Terminal(_) => (reward.0, None),
+            };
+            let step_size = alpha.rate(increment(&mut counts, &state, &action));
+            let value = q.entry((state, action)).or_insert(0.0);
+            *value += step_size * (target - *value);
+
+            match next {
+                Some((next_state, next_action)) => {
+                    state = next_state;
+                    action = next_action;
+                }
+                None => break,
+            }
+        }
+    }
+    q
+}
+
+/// Returns the action-value function learned by SARSA, on-policy TD control, over
+/// `num_episodes` episodes of interaction with `mdp`.
+///
+/// Each episode starts in a uniformly random non-terminal state and follows the ε-greedy
+/// policy with respect to the current estimate, with ε = `epsilon` / k in the k-th episode
+/// so that exploration decays. After each step the estimate is updated as
+/// Q(s, a) ← Q(s, a) + α (r + γQ(s', a') - Q(s, a)), where a' is the next action taken and
+/// the value of terminal states is zero, and α is the step size given by `alpha` for the
+/// number of updates made to (s, a). A successor without actions is treated as terminal.
+/// Episodes are cut off after [`MAX_EPISODE_STEPS`] steps. All random choices are drawn
+/// using `rng`, so the result is reproducible when it is seeded.
+pub fn sarsa<S, A, X, R>(
+    mdp: &FiniteMarkovDecisionProcess<S, A, X>,
+    gamma: f64,
+    alpha: LearningRate,
+    epsilon: f64,
+    num_episodes: usize,
+    rng: &mut R,
+) -> ActionValueFunction<S, A>
+where
+    S: Ord + Hash + Clone,
+    A: Ord + Hash + Clone,
+    X: FiniteDistribution<(State<S>, Reward)>,
+    R: Rng + ?Sized,
+{
+    td_control(
+        mdp,
+        gamma,
+        alpha,
+        epsilon,
+        num_episodes,
+        |q, state, _, action, _| {
+            q.get(&(state.clone(), action.clone()))
+                .copied()
+                .unwrap_or(0.0)
+        },
+        rng,
+    )
+}
+
+/// Returns the action-value function learned by Q-learning, off-policy TD control, over
+/// `num_episodes` episodes of interaction with `mdp`.
+///
+/// Episodes are generated as in [`sarsa`] by an ε-greedy behavior policy, but the target
+/// policy is greedy: the estimate is updated as
+/// Q(s, a) ← Q(s, a) + α (r + γ maxₐ' Q(s', a') - Q(s, a)).
+pub fn q_learning<S, A, X, R>(
+    mdp: &FiniteMarkovDecisionProcess<S, A, X>,
+    gamma: f64,
+    alpha: LearningRate,
+    epsilon: f64,
+    num_episodes: usize,
+    rng: &mut R,
+) -> ActionValueFunction<S, A>
+where
+    S: Ord + Hash + Clone,
+    A: Ord + Hash + Clone,
+    X: FiniteDistribution<(State<S>, Reward)>,
+    R: Rng + ?Sized,
+{
+    td_control(
+        mdp,
+        gamma,
+        alpha,
+        epsilon,
+        num_episodes,
+        |q, state, actions, _, _| {
+            greedy_action(q, state, actions)
+                .and_then(|best| q.get(&(state.clone(), best.clone())))
+                .copied()
+                .unwrap_or(0.0)
+        },
+        rng,
+    )
+}
+
+/// Returns the action-value function learned by Expected SARSA over `num_episodes`
+/// episodes of interaction with `mdp`.
+///
+/// Episodes are generated as in [`sarsa`], but the target averages over the next action
+/// under the current ε-greedy policy π instead of using the sampled one: the estimate is
+/// updated as Q(s, a) ← Q(s, a) + α (r + γ Σₐ' π(a'|s') Q(s', a') - Q(s, a)). With ε = 0
+/// this is the Q-learning update.
+pub fn expected_sarsa<S, A, X, R>(
+    mdp: &FiniteMarkovDecisionProcess<S, A, X>,
+    gamma: f64,
+    alpha: LearningRate,
+    epsilon: f64,
+    num_episodes: usize,
+    rng: &mut R,
+) -> ActionValueFunction<S, A>
+where
+    S: Ord + Hash + Clone,
+    A: Ord + Hash + Clone,
+    X: FiniteDistribution<(State<S>, Reward)>,
+    R: Rng + ?Sized,
+{
+    td_control(
+        mdp,
+        gamma,
+        alpha,
+        epsilon,
+        num_episodes,
+        |q, state, actions, _, epsilon| expected_value(q, state, actions, epsilon),
+        rng,
+    )
+}
+
+/// Returns the expected value of `q` in `state` under the ε-greedy policy over `actions`,
+/// or zero if `actions` is empty so that such a state contributes no bootstrap term.
+fn expected_value<S, A>(
+    q: &ActionValueFunction<S, A>,
+    state: &NonTerminal<S>,
+    actions: &[A],
+    epsilon: f64,
+) -> f64
+where
+    S: Ord + Hash + Clone,
+    A: Ord + Hash + Clone,
+{
+    let Some(best) = greedy_action(q, state, actions) else {
+        return 0.0;
+    };
+    let value = |action: &A| {
+        q.get(&(state.clone(), action.clone()))
+            .copied()
+            .unwrap_or(0.0)
+    };
+    let mean = actions.iter().map(value).sum::<f64>() / actions.len() as f64;
+    epsilon * mean + (1.0 - epsilon) * value(best)
+}
+
+/// Returns the action-value function learned by Q-learning with experience replay over
+/// `num_episodes` episodes of interaction with `mdp`.
+///
+/// Episodes are generated as in [`sarsa`]. Each observed transition is added to an
+/// [`ExperienceReplay`] buffer of `capacity` transitions, and after every step a batch of
+/// `batch_size` transitions is sampled from the buffer and each of them is applied in turn
+/// as the update Q(s, a) ← Q(s, a) + α (r + γ maxₐ' Q(s', a') - Q(s, a)) of
+/// [`q_learning`].
+///
+/// Panics if `capacity` is zero.
+#[allow(clippy::too_many_arguments)]
+pub fn q_learning_replay<S, A, X, R>(
+    mdp: &FiniteMarkovDecisionProcess<S, A, X>,
+    gamma: f64,
+    alpha: LearningRate,
+    epsilon: f64,
+    num_episodes: usize,
+    capacity: usize,
+    batch_size: usize,
+    rng: &mut R,
+) -> ActionValueFunction<S, A>
+where
+    S: Ord + Hash + Clone,
+    A: Ord + Hash + Clone,
+    X: FiniteDistribution<(State<S>, Reward)>,
+    R: Rng + ?Sized,
+{
+    let mut replay = ExperienceReplay::new(capacity);
+    let states = mdp.non_terminal_states();
+    let mut q: ActionValueFunction<S, A> = HashMap::new();
+    let mut counts: HashMap<(NonTerminal<S>, A), usize> = HashMap::new();
+    if states.is_empty() {
+        return q;
+    }
+
+    for k in 1..=num_episodes {
+        let epsilon = epsilon / k as f64;
+        let mut state = states[rng.gen_range(0..states.len())].clone();
+
+        for _ in 0..MAX_EPISODE_STEPS {
+            let Some(action) =
+                epsilon_greedy_action(&q, &state, &mdp.actions(&state), epsilon, rng)
+            else {
+                break;
+            };
+            let step = mdp
+                .step(&state, &action)
+                .expect("action is available in state");
+            let (next_state, reward) = step.sample_with(rng);
+            replay.push((state, action, reward.0, next_state.clone()));
+
+            for (s, a, r, next) in replay.sample_batch(batch_size, rng) {
+                let step_size = alpha.rate(increment(&mut counts, s, a));
+                q_learning_update(&mut q, mdp, gamma, step_size, (s, a, *r, next));
+            }
+
+            match next_state {
+                State::NonTerminal(next_state) => state = next_state,
+                State::Terminal(_) => break,
+            }
+        }
+    }
+    q
+}
+
+/// Returns the action-value function learned by Dyna-Q over `num_episodes` episodes of
+/// interaction with `mdp`, which combines Q-learning with planning from a learned model.
+///
+/// Episodes are generated as in [`sarsa`], and each real transition is used for the
+/// update of [`q_learning`]. It is also recorded in a model that keeps the most recent
+/// reward and next state observed for each pair (s, a). After every real step,
+/// `planning_steps` further updates are made from transitions simulated by the model,
+/// each for a pair chosen uniformly among those observed so far. With `planning_steps` = 0
+/// this is Q-learning. All random choices are drawn using `rng`.
+#[allow(clippy::too_many_arguments)]
+pub fn dyna_q<S, A, X, R>(
+    mdp: &FiniteMarkovDecisionProcess<S, A, X>,
+    gamma: f64,
+    alpha: LearningRate,
+    epsilon: f64,
+    planning_steps: usize,
+    num_episodes: usize,
+    rng: &mut R,
+) -> ActionValueFunction<S, A>
+where
+    S: Ord + Hash + Clone,
+    A: Ord + Hash + Clone,
+    X: FiniteDistribution<(State<S>, Reward)>,
+    R: Rng + ?Sized,
+{
+    let states = mdp.non_terminal_states();
+    let mut q: ActionValueFunction<S, A> = HashMap::new();
+    let mut counts: HashMap<(NonTerminal<S>, A), usize> = HashMap::new();
+    let mut model: HashMap<(NonTerminal<S>, A), (f64, State<S>)> = HashMap::new();
+    let mut observed: Vec<(NonTerminal<S>, A)> = Vec::new();
+    if states.is_empty() {
+        return q;
+    }
+
+    for k in 1..=num_episodes {
+        let epsilon = epsilon / k as f64;
+        let mut state = states[rng.gen_range(0..states.len())].clone();
+
+        for _ in 0..MAX_EPISODE_STEPS {
+            let Some(action) =
+                epsilon_greedy_action(&q, &state, &mdp.actions(&state), epsilon, rng)
+            else {
+                break;
+            };
+            let step = mdp
+                .step(&state, &action)
+                .expect("action is available in state");
+            let (next_state, reward) = step.sample_with(rng);
+
+            let step_size = alpha.rate(increment(&mut counts, &state, &action));
+            q_learning_update(
+                &mut q,
+                mdp,
+                gamma,
+                step_size,
+                (&state, &action, reward.0, &next_state),
+            );
+            let key = (state, action);
+            if model
+                .insert(key.clone(), (reward.0, next_state.clone()))
+                .is_none()
+            {
+                observed.push(key);
+            }
+
+            for _ in 0..planning_steps {
+                let (s, a) = &observed[rng.gen_range(0..observed.len())];
+                let (r, next) = &model[&(s.clone(), a.clone())];
+                let step_size = alpha.rate(increment(&mut counts, s, a));
+                q_learning_update(&mut q, mdp, gamma, step_size, (s, a, *r, next));
+            }
+
+            match next_state {
+                State::NonTerminal(next_state) => state = next_state,
+                State::Terminal(_) => break,
+            }
+        }
+    }
+    q
+}
+
+/// Applies the Q-learning update Q(s, a) ← Q(s, a) + α (r + γ maxₐ' Q(s', a') - Q(s, a))
+/// to `q` for the transition `(s, a, r, s')`, where the value of a terminal successor, or
+/// of one without actions in `mdp`, is zero.
+fn q_learning_update<S, A, X>(
+    q: &mut ActionValueFunction<S, A>,
+    mdp: &FiniteMarkovDecisionProcess<S, A, X>,
+    gamma: f64,
+    alpha: f64,
+    (state, action, reward, next_state): (&NonTerminal<S>, &A, f64, &State<S>),
+) where
+    S: Ord + Hash + Clone,
+    A: Ord + Hash + Clone,
+    X: FiniteDistribution<(State<S>, Reward)>,
+{
+    let next_value = match next_state {
+        State::NonTerminal(next_state) => {
+            let actions = mdp.actions(next_state);
+            greedy_action(q, next_state, &actions)
+                .and_then(|best| q.get(&(next_state.clone(), best.clone())))
+                .copied()
+                .unwrap_or(0.0)
+        }
+        State::Terminal(_) => 0.0,
+    };
+    let value = q.entry((state.clone(), action.clone())).or_insert(0.0);
+    *value += alpha * (reward + gamma * next_value - *value);
+}
+
+// --------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distribution::Constant;
+    use crate::markov_process::Terminal;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    type Step = Constant<(State<u32>, Reward)>;
+
+    /// A corridor of cells 0 and 1 with the goal 2 to the right of cell 1, worth 10. Every
+    /// move costs 1, and moving left from cell 0 bumps into the wall and stays put.
+    fn corridor() -> FiniteMarkovDecisionProcess<u32, char, Step> {
+        let non_terminal = |s| State::NonTerminal(NonTerminal::new(s));
+        let step = |next, reward| Constant::new((next, Reward(reward)));
+        FiniteMarkovDecisionProcess::new(HashMap::from([
+            (
+                0,
+                HashMap::from([
+                    ('L', step(non_terminal(0), -1.0)),
+                    ('R', step(non_terminal(1), -1.0)),
+                ]),
+            ),
+            (
+                1,
+                HashMap::from([
+                    ('L', step(non_terminal(0), -1.0)),
+                    ('R', step(State::Terminal(Terminal::new(2)), 9.0)),
+                ]),
+            ),
+        ]))
+        .unwrap()
+    }
+
+    /// An episode of a reward process as a sequence of `(state, reward, next_state)`.
+    type Episode = Vec<(NonTerminal<u32>, f64, State<u32>)>;
+
+    /// Returns `num_episodes` episodes of the random walk on states 1 to 5 that starts in
+    /// state 3 and moves left or right with equal probability until it reaches the
+    /// terminal state 0 or 6, with reward 1 for reaching 6 and 0 otherwise. Without
+    /// discounting state s has value s / 6.
+    fn random_walk(num_episodes: usize, seed: u64) -> Vec<Episode> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..num_episodes)
+            .map(|_| {
+                let mut episode = Vec::new();
+                let mut state = 3;
+                loop {
+                    let next_state = if rng.gen_bool(0.5) {
+                        state + 1
+                    } else {
+                        state - 1
+                    };
+                    let reward = if next_state == 6 { 1.0 } else { 0.0 };
+                    if next_state == 0 || next_state == 6 {
+                        let next = State::Terminal(Terminal::new(next_state));
+                        episode.push((NonTerminal::new(state), reward, next));
+                        break episode;
+                    }
+                    let next = State::NonTerminal(NonTerminal::new(next_state));
+                    episode.push((NonTerminal::new(state), reward, next));
+                    state = next_state;
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn td_zero_approaches_the_value_of_a_random_walk() {
+        let episodes = random_walk(5_000, 13);
+        let vf = td_zero(episodes, 1.0, LearningRate::Constant(0.01));
+        for s in 1..=5 {
+            let value = vf[&NonTerminal::new(s)];
+            let expected = s as f64 / 6.0;
+            assert!((value - expected).abs() < 0.05, "V({s}) = {value}");
+        }
+    }
+
+    #[test]
+    fn seeded_control_is_reproducible() {
+        // Each run builds its own process, so the results must not depend on the
+        // iteration order of its maps.
+        let learn = |seed| {
+            let mdp = corridor();
+            let alpha = LearningRate::Constant(0.5);
+            let rng = &mut StdRng::seed_from_u64(seed);
+            vec![
+                sarsa(&mdp, 0.9, alpha, 0.5, 20, rng),
+                q_learning(&mdp, 0.9, alpha, 0.5, 20, rng),
+                expected_sarsa(&mdp, 0.9, alpha, 0.5, 20, rng),
+                q_learning_replay(&mdp, 0.9, alpha, 0.5, 20, 16, 4, rng),
+                dyna_q(&mdp, 0.9, alpha, 0.5, 4, 20, rng),
+            ]
+        };
+        assert_eq!(learn(21), learn(21));
+    }
+
+    #[test]
+    fn epsilon_greedy_action_of_no_actions_is_none() {
+        let q: ActionValueFunction<u32, char> = HashMap::new();
+        let rng = &mut StdRng::seed_from_u64(0);
+        let state = NonTerminal::new(0);
+        assert_eq!(epsilon_greedy_action(&q, &state, &[], 0.5, rng), None);
+        assert_eq!(epsilon_greedy_action(&q, &state, &[], 0.0, rng), None);
+    }
+
+    #[test]
+    fn expected_value_of_no_actions_is_zero() {
+        let q: ActionValueFunction<u32, char> = HashMap::new();
+        assert_eq!(expected_value(&q, &NonTerminal::new(0), &[], 0.5), 0.0);
+    }
+
+    #[test]
+    fn expected_value_mixes_mean_and_greedy_values() {
+        let state = NonTerminal::new(0);
+        let q = HashMap::from([((state, 'L'), -2.0), ((state, 'R'), 4.0)]);
+        assert!((expected_value(&q, &state, &['L', 'R'], 0.5) - 2.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn q_learning_update_treats_successor_without_actions_as_terminal() {
+        let mdp = corridor();
+        let mut q = HashMap::new();
+        let state = NonTerminal::new(0);
+        let dangling = State::NonTerminal(NonTerminal::new(5));
+        q_learning_update(&mut q, &mdp, 0.9, 0.5, (&state, &'R', 3.0, &dangling));
+        assert_eq!(q[&(state, 'R')], 1.5);
+    }
+
+    #[test]
+    fn sarsa_learns_to_walk_towards_the_goal() {
+        let mdp = corridor();
+        let rng = &mut StdRng::seed_from_u64(7);
+        let q = sarsa(&mdp, 0.9, LearningRate::Constant(0.5), 0.5, 500, rng);
+        for state in mdp.mapping().keys() {
+            let actions = mdp.actions(state);
+            assert_eq!(greedy_action(&q, state, &actions), Some(&'R'));
+        }
+    }
+
+    #[test]
+    fn q_learning_converges_to_the_optimal_action_values() {
+        use crate::convergence::Convergence;
+        use crate::dynamic_programming::{action_value_function, value_iteration};
+
+        let mdp = corridor();
+        let (policy, _, _) =
+            value_iteration(&mdp, 0.9, Convergence::new(1e-12, 10_000), None).unwrap();
+        let optimal = action_value_function(&mdp, &policy, 0.9).unwrap();
+
+        // With ε = `epsilon` / k never falling below 1 the behavior policy stays uniformly
+        // random, so every pair keeps being visited and, as Q-learning is off-policy, the
+        // whole table converges to the optimal action values.
+        let num_episodes = 2_000;
+        let rng = &mut StdRng::seed_from_u64(17);
+        let q = q_learning(
+            &mdp,
+            0.9,
+            LearningRate::Constant(0.5),
+            num_episodes as f64,
+            num_episodes,
+            rng,
+        );
+        assert_eq!(q.len(), optimal.len());
+        for (key, value) in &optimal {
+            assert!((q[key] - value).abs() < 1e-6, "{key:?}: {}", q[key]);
+        }
+    }
+
+    #[test]
+    fn td_lambda_spans_td_zero_and_monte_carlo() {
+        use crate::monte_carlo::{mc_prediction, VisitType};
+
+        let episodes = random_walk(2_000, 44);
+        let alpha = LearningRate::Constant(0.01);
+        assert_eq!(
+            td_lambda(episodes.clone(), 1.0, 0.0, alpha),
+            td_zero(episodes.clone(), 1.0, alpha)
+        );
+
+        let returns = episodes
+            .iter()
+            .map(|episode| episode.iter().map(|(state, reward, _)| (*state, *reward)));
+        let mc = mc_prediction(returns, 1.0, VisitType::Every);
+        let td = td_lambda(episodes, 1.0, 1.0, alpha);
+        for (state, value) in &mc {
+            assert!(
+                (td[state] - value).abs() < 0.05,
+                "{state:?}: {} vs {value}",
+                td[state]
+            );
+        }
+    }
+
+    #[test]
+    fn one_step_td_is_td_zero() {
+        let episodes = random_walk(500, 45);
+        for alpha in [LearningRate::Constant(0.1), LearningRate::CountBased] {
+            assert_eq!(
+                n_step_td(episodes.clone(), 0.9, alpha, 1),
+                td_zero(episodes.clone(), 0.9, alpha)
+            );
+        }
+    }
+
+    #[test]
+    fn tabular_td_zero_matches_hash_map_updates_exactly() {
+        let episodes = random_walk(500, 47);
+        let alpha = LearningRate::CountBased;
+
+        // The TD(0) updates written directly against a HashMap.
+        let mut expected: ValueFunction<u32> = HashMap::new();
+        let mut counts: HashMap<NonTerminal<u32>, usize> = HashMap::new();
+        for (state, reward, next_state) in episodes.iter().flatten() {
+            let next_value = match next_state {
+                State::NonTerminal(next_state) => expected.get(next_state).copied().unwrap_or(0.0),
+                State::Terminal(_) => 0.0,
+            };
+            let count = counts.entry(*state).or_insert(0);
+            *count += 1;
+            let step_size = alpha.rate(*count);
+            let value = expected.entry(*state).or_insert(0.0);
+            *value += step_size * (reward + 0.9 * next_value - *value);
+        }
+
+        assert_eq!(td_zero(episodes.clone(), 0.9, alpha), expected);
+        let tabular = td_zero_approx(episodes, 0.9, alpha, Tabular::new());
+        assert_eq!(tabular.into_values(), expected);
+    }
+
+    #[test]
+    fn expected_sarsa_learns_to_walk_towards_the_goal() {
+        let mdp = corridor();
+        let rng = &mut StdRng::seed_from_u64(67);
+        let q = expected_sarsa(&mdp, 0.9, LearningRate::Constant(0.5), 0.5, 500, rng);
+        for state in mdp.mapping().keys() {
+            let actions = mdp.actions(state);
+            assert_eq!(greedy_action(&q, state, &actions), Some(&'R'));
+        }
+    }
+
+    #[test]
+    fn replay_buffer_evicts_oldest_and_samples_only_held_transitions() {
+        let transition = |i: u32| {
+            let next_state = State::NonTerminal(NonTerminal::new(i + 1));
+            (NonTerminal::new(i), 'R', i as f64, next_state)
+        };
+        let mut buffer = ExperienceReplay::new(3);
+        let rng = &mut StdRng::seed_from_u64(68);
+        assert!(buffer.is_empty());
+        assert!(buffer.sample_batch(4, rng).is_empty());
+
+        for i in 0..5 {
+            buffer.push(transition(i));
+        }
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(buffer.capacity(), 3);
+        let held: Vec<u32> = buffer.iter().map(|(state, ..)| *state.state()).collect();
+        assert_eq!(held, vec![2, 3, 4]);
+
+        let batch = buffer.sample_batch(100, rng);
+        assert_eq!(batch.len(), 100);
+        assert!(batch.iter().all(|t| (2..5).contains(t.0.state())));
+    }
+
+    #[test]
+    fn polynomial_decay_satisfies_robbins_monro() {
+        let alpha = LearningRate::PolynomialDecay {
+            base: 1.0,
+            exponent: 0.75,
+        };
+        let rates: Vec<f64> = (1..=1_000_000).map(|n| alpha.rate(n)).collect();
+        assert!(rates.windows(2).all(|pair| pair[1] < pair[0]));
+
+        // Σ n^-0.75 grows like 4 n^0.25 without bound, while Σ n^-1.5 converges to ζ(1.5).
+        let partial_sums = |n: usize| -> (f64, f64) {
+            let rates = &rates[..n];
+            (rates.iter().sum(), rates.iter().map(|a| a * a).sum())
+        };
+        let (sum_small, squares_small) = partial_sums(10_000);
+        let (sum_large, squares_large) = partial_sums(1_000_000);
+        assert!(sum_large > 2.5 * sum_small, "{sum_small} vs {sum_large}");
+        assert!(squares_large - squares_small < 0.03);
+        assert!(squares_large < 2.613);
+
+        assert_eq!(LearningRate::CountBased.rate(4), 0.25);
+        assert_eq!(LearningRate::Constant(0.1).rate(1_000), 0.1);
+    }
+
+    #[test]
+    fn dyna_q_learns_to_walk_towards_the_goal() {
+        let mdp = corridor();
+        let rng = &mut StdRng::seed_from_u64(11);
+        let q = dyna_q(&mdp, 0.9, LearningRate::Constant(0.5), 0.5, 8, 200, rng);
+        for state in mdp.mapping().keys() {
+            let actions = mdp.actions(state);
+            assert_eq!(greedy_action(&q, state, &actions), Some(&'R'));
+        }
+    }
+
+    #[test]
+    fn dyna_q_needs_fewer_episodes_than_q_learning() {
+        use crate::convergence::Convergence;
+        use crate::dynamic_programming::{action_value_function, value_iteration};
+        use crate::gridworld::{Cell, Direction, GridworldBuilder};
+
+        let mdp = GridworldBuilder::new(1, 6)
+            .terminal((0, 5), 10.0)
+            .step_cost(1.0)
+            .build()
+            .unwrap();
+        let (policy, _, _) =
+            value_iteration(&mdp, 0.9, Convergence::new(1e-12, 10_000), None).unwrap();
+        let optimal = action_value_function(&mdp, &policy, 0.9).unwrap();
+        // The largest error in the value of an optimal action.
+        let error = |q: &ActionValueFunction<Cell, Direction>| {
+            mdp.mapping()
+                .keys()
+                .map(|state| {
+                    let key = (*state, *policy.action_for(state).unwrap());
+                    (q.get(&key).copied().unwrap_or(0.0) - optimal[&key]).abs()
+                })
+                .fold(0.0, f64::max)
+        };
+
+        let alpha = LearningRate::Constant(0.5);
+        let rng = &mut StdRng::seed_from_u64(85);
+        let planned = error(&dyna_q(&mdp, 0.9, alpha, 0.5, 20, 20, rng));
+        let rng = &mut StdRng::seed_from_u64(85);
+        let unplanned = error(&q_learning(&mdp, 0.9, alpha, 0.5, 20, rng));
+        assert!(planned < 0.01, "{planned}");
+        assert!(unplanned > 10.0 * planned, "{unplanned} vs {planned}");
+    }
+}